@@ -14,15 +14,46 @@ use proc_macro::TokenStream;
 /// - `consumes=<literal>` or `consumes=[<expr>, ...]`: specifies resources
 ///   which are "consumed" (exclusively)
 ///
+/// - `consumes_any=[<expr>, ...]`: consumes one of the given resources;
+///   whichever is available first
+///
 /// - `no_default_uses`: removes basic resources from the "uses" list
 ///
 /// - `notparallel`: consumes basic resources so that test is not run with other ones
 ///
+/// - `priority=<expr>`: tests with a higher priority get resources first
+///
 ///
 /// - `timeout=<expr>`: test panics after the given time when not finished
 ///
+/// - `lock_timeout=<expr>`: test panics when resources could not be reserved
+///   within the given time
+///
+///
+/// - `after=[<path>, ...]`: test starts after the given tests completed
+///
+/// - `before=[<path>, ...]`: the given tests start after this test completed
+///
 /// See etest crate documentation for details.
 #[proc_macro_attribute]
 pub fn etest(attr: TokenStream, item: TokenStream) -> TokenStream {
     etest_impl::etest(attr, item)
 }
+
+/// Derives `From<T> for etest::ResourceId` for enums and structs
+///
+/// The id is the name of the struct or of the enum variant in snake case;
+/// e.g. `Output::Hdmi` becomes `"hdmi"`.  Fields are appended as components
+/// of a hierarchical id; e.g. `Port(3)` becomes `"port/3"`.
+///
+/// Supported attributes on structs and enum variants:
+///
+/// - `#[resource(name = "<template>")]`: overrides the name.  It can refer to
+///   fields by `{<field>}` placeholders (e.g. `"usb/hub{hub}/port{port}"` or
+///   `"port{0:02}"`); fields are not appended then.
+///
+/// Fields must implement `Display`.
+#[proc_macro_derive(ResourceId, attributes(resource))]
+pub fn derive_resource_id(item: TokenStream) -> TokenStream {
+    etest_impl::derive_resource_id(item)
+}
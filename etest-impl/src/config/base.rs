@@ -11,8 +11,13 @@ pub struct Config {
     pub skip_fn:	Option<TokenStream>,
     pub skip_result:	Option<TokenStream>,
     pub timeout:	Option<TokenStream>,
+    pub lock_timeout:	Option<TokenStream>,
+    pub priority:	Option<TokenStream>,
     pub uses:		TokenSet,
     pub consumes:	TokenSet,
+    pub consumes_any:	TokenSet,
+    pub after:		TokenSet,
+    pub before:		TokenSet,
 }
 
 impl Config {
@@ -42,8 +47,13 @@ impl Config {
                 "skip"		=> res.skip_fn       = cfg.convert::<TokenStream>()?,
                 "skip_result"	=> res.skip_result   = cfg.convert::<TokenStream>()?,
                 "timeout"	=> res.timeout       = cfg.convert::<TokenStream>()?,
+                "lock_timeout"	=> res.lock_timeout  = cfg.convert::<TokenStream>()?,
+                "priority"	=> res.priority      = cfg.convert::<TokenStream>()?,
                 "uses"		=> res.uses          = cfg.convert::<TokenSet>()?.unwrap(),
                 "consumes"	=> res.consumes      = cfg.convert::<TokenSet>()?.unwrap(),
+                "consumes_any"	=> res.consumes_any  = cfg.convert::<TokenSet>()?.unwrap(),
+                "after"		=> res.after         = cfg.convert::<TokenSet>()?.unwrap(),
+                "before"	=> res.before        = cfg.convert::<TokenSet>()?.unwrap(),
                 "notparallel"	=> notparallel       = true,
                 c		=> return Err(err(Span::call_site(), &format!("unsupported key: {c:?}")))
             }
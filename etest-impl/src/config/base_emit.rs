@@ -1,9 +1,26 @@
-use proc_macro::{Delimiter, Group, Ident, Punct, Spacing, Span, TokenStream, TokenTree};
+use proc_macro::{Delimiter, Group, Ident, Literal, Punct, Spacing, Span, TokenStream, TokenTree};
 
-use super::Config;
+use super::{ Config, TokenSet };
 
 use crate::defs::*;
 use crate::Function;
+use crate::utils::err;
+
+/// Returns the paths in `set` as comma separated string literals
+fn test_names(set: &TokenSet) -> TokenStream {
+    let mut res = Vec::new();
+
+    for path in set.iter() {
+        let name: String = path.clone().into_iter().map(|t| t.to_string()).collect();
+
+        res.extend([
+            TokenTree::Literal(Literal::string(&name)),
+            TokenTree::Punct(Punct::new(',', Spacing::Alone)),
+        ]);
+    }
+
+    res.into_iter().collect()
+}
 
 impl Config {
     /// Adds the `#[test]` attribute
@@ -138,6 +155,20 @@ impl Config {
         ].into_iter().collect()
     }
 
+    /// Returns from a skipped test; e.g. `return 23`
+    fn emit_skip_return(&self, func: &Function) -> TokenStream {
+        let mut res = vec![
+            TokenTree::Ident(Ident::new("return", Span::call_site())),
+        ];
+
+        match &self.skip_result {
+            Some(r)	=> res.extend(r.clone()),
+            None	=> res.extend(func.default_return()),
+        }
+
+        res.into_iter().collect()
+    }
+
     /// Adds a check whether test shall be skipped.
     ///
     /// Used configuration parameters:
@@ -181,15 +212,32 @@ impl Config {
                 TokenTree::Ident(Ident::new(VARNAME_CURENT_TEST, Span::mixed_site()))
             ].into_iter().collect())),
             TokenTree::Punct(Punct::new(';', Spacing::Alone)),
-
-            TokenTree::Ident(Ident::new("return", Span::call_site())),
         ];
 
-        match &self.skip_result {
-            Some(r)	=> inner_block.extend(r.clone()),
-            None	=> inner_block.extend(func.default_return()),
+        // 'etest::TestOrder::skipped(module_path!(), "test");' lets tests
+        // which must run after this one start
+        if self.has_test_fn() {
+            inner_block.extend([
+                TokenTree::Ident(Ident::new(CRATE_NAME, Span::mixed_site())),
+                TokenTree::Punct(Punct::new(':', Spacing::Joint)),
+                TokenTree::Punct(Punct::new(':', Spacing::Alone)),
+                TokenTree::Ident(Ident::new("TestOrder", Span::mixed_site())),
+                TokenTree::Punct(Punct::new(':', Spacing::Joint)),
+                TokenTree::Punct(Punct::new(':', Spacing::Alone)),
+                TokenTree::Ident(Ident::new("skipped", Span::mixed_site())),
+                TokenTree::Group(Group::new(Delimiter::Parenthesis, [
+                    TokenTree::Ident(Ident::new("module_path", Span::mixed_site())),
+                    TokenTree::Punct(Punct::new('!', Spacing::Alone)),
+                    TokenTree::Group(Group::new(Delimiter::Parenthesis, TokenStream::new())),
+                    TokenTree::Punct(Punct::new(',', Spacing::Alone)),
+                    TokenTree::Literal(Literal::string(&func.name)),
+                ].into_iter().collect())),
+                TokenTree::Punct(Punct::new(';', Spacing::Alone)),
+            ]);
         }
 
+        inner_block.extend(self.emit_skip_return(func));
+
         // final, outer block
         [
             TokenTree::Ident(Ident::new("if", Span::mixed_site())),
@@ -201,11 +249,149 @@ impl Config {
         ].into_iter().collect()
     }
 
-    pub fn emit_lock(&self, _func: &Function) -> TokenStream {
+    /// Registers the test for being ordered against other ones
+    ///
+    /// Used configuration parameters:
+    ///
+    /// - `after`: tests which must complete before this test starts; only
+    ///   checked here
+    ///
+    /// - `before`: tests which must not start before this test completed
+    ///
+    /// # Example
+    ///
+    /// ```ignore
+    /// #[etest(after=[provision], before=[super::cleanup])]
+    /// fn test() { /* ... */ }
+    /// ```
+    ///
+    /// expands to
+    ///
+    /// ```ignore
+    /// fn test() {
+    ///     etest::register_test!("test", false, ["super::cleanup"]);
+    ///     let _ = (provision, super::cleanup,);
+    ///     /* ... */
+    /// }
+    /// ```
+    pub fn emit_register(&self, func: &Function) -> TokenStream {
+        if !self.has_test_fn() {
+            if self.after.is_empty() && self.before.is_empty() {
+                return TokenStream::new();
+            }
+
+            return err(Span::call_site(), "'after' and 'before' require a test function");
+        }
+
+        // 'etest::register_test!("test", false, ["before"]);'
+        let mut res = vec![
+            TokenTree::Ident(Ident::new(CRATE_NAME, Span::mixed_site())),
+            TokenTree::Punct(Punct::new(':', Spacing::Joint)),
+            TokenTree::Punct(Punct::new(':', Spacing::Alone)),
+            TokenTree::Ident(Ident::new("register_test", Span::mixed_site())),
+            TokenTree::Punct(Punct::new('!', Spacing::Alone)),
+            TokenTree::Group(Group::new(Delimiter::Parenthesis, [
+                TokenTree::Literal(Literal::string(&func.name)),
+                TokenTree::Punct(Punct::new(',', Spacing::Alone)),
+                TokenTree::Ident(Ident::new(&func.is_ignored().to_string(), Span::mixed_site())),
+                TokenTree::Punct(Punct::new(',', Spacing::Alone)),
+                TokenTree::Group(Group::new(Delimiter::Bracket, test_names(&self.before))),
+            ].into_iter().collect())),
+            TokenTree::Punct(Punct::new(';', Spacing::Alone)),
+        ];
+
+        // 'let _ = (after, before,);' lets the compiler check the paths
+        if !self.after.is_empty() || !self.before.is_empty() {
+            let mut paths = Vec::new();
+
+            for p in self.after.iter().chain(self.before.iter()) {
+                paths.extend(p.clone());
+                paths.push(TokenTree::Punct(Punct::new(',', Spacing::Alone)));
+            }
+
+            res.extend([
+                TokenTree::Ident(Ident::new("let", Span::mixed_site())),
+                TokenTree::Ident(Ident::new("_", Span::mixed_site())),
+                TokenTree::Punct(Punct::new('=', Spacing::Alone)),
+                TokenTree::Group(Group::new(Delimiter::Parenthesis, paths.into_iter().collect())),
+                TokenTree::Punct(Punct::new(';', Spacing::Alone)),
+            ]);
+        }
+
+        res.into_iter().collect()
+    }
+
+    /// Waits until the tests which must run before this one have completed;
+    /// the test is registered by [`emit_register()`](Self::emit_register).
+    ///
+    /// # Example
+    ///
+    /// ```ignore
+    /// #[etest(after=[provision])]
+    /// fn test() { /* ... */ }
+    /// ```
+    ///
+    /// expands to
+    ///
+    /// ```ignore
+    /// fn test() {
+    ///     let _etest_order = etest::TestOrder::start(module_path!(), "test", &["provision"]);
+    ///     /* ... */
+    /// }
+    /// ```
+    pub fn emit_order(&self, func: &Function) -> TokenStream {
+        if !self.has_test_fn() {
+            return TokenStream::new();
+        }
+
+        // async tests must not block the worker threads of the runtime
+        let start = match func.is_async {
+            true	=> "start_async",
+            false	=> "start",
+        };
+
+        // 'let _etest_order = etest::TestOrder::start(module_path!(), "test", &["after"]);'
+        let mut res = vec![
+            TokenTree::Ident(Ident::new("let", Span::mixed_site())),
+            TokenTree::Ident(Ident::new("_etest_order", Span::mixed_site())),
+            TokenTree::Punct(Punct::new('=', Spacing::Alone)),
+
+            TokenTree::Ident(Ident::new(CRATE_NAME, Span::mixed_site())),
+            TokenTree::Punct(Punct::new(':', Spacing::Joint)),
+            TokenTree::Punct(Punct::new(':', Spacing::Alone)),
+            TokenTree::Ident(Ident::new("TestOrder", Span::mixed_site())),
+            TokenTree::Punct(Punct::new(':', Spacing::Joint)),
+            TokenTree::Punct(Punct::new(':', Spacing::Alone)),
+            TokenTree::Ident(Ident::new(start, Span::mixed_site())),
+            TokenTree::Group(Group::new(Delimiter::Parenthesis, [
+                TokenTree::Ident(Ident::new("module_path", Span::mixed_site())),
+                TokenTree::Punct(Punct::new('!', Spacing::Alone)),
+                TokenTree::Group(Group::new(Delimiter::Parenthesis, TokenStream::new())),
+                TokenTree::Punct(Punct::new(',', Spacing::Alone)),
+                TokenTree::Literal(Literal::string(&func.name)),
+                TokenTree::Punct(Punct::new(',', Spacing::Alone)),
+                TokenTree::Punct(Punct::new('&', Spacing::Alone)),
+                TokenTree::Group(Group::new(Delimiter::Bracket, test_names(&self.after))),
+            ].into_iter().collect())),
+        ];
+
+        if func.is_async {
+            res.extend([
+                TokenTree::Punct(Punct::new('.', Spacing::Alone)),
+                TokenTree::Ident(Ident::new("await", Span::mixed_site())),
+            ]);
+        }
+
+        res.push(TokenTree::Punct(Punct::new(';', Spacing::Alone)));
+
+        res.into_iter().collect()
+    }
+
+    pub fn emit_lock(&self, func: &Function) -> TokenStream {
         //println!("uses={:?}", self.uses);
         //println!("consumes={:?}", self.consumes);
 
-        if self.uses.is_empty() && self.consumes.is_empty() {
+        if self.uses.is_empty() && self.consumes.is_empty() && self.consumes_any.is_empty() {
             return TokenStream::new();
         }
 
@@ -240,9 +426,39 @@ impl Config {
             ]);
         }
 
+        for c in self.consumes_any.iter() {
+            builder.extend([
+                TokenTree::Punct(Punct::new('.', Spacing::Alone)),
+                TokenTree::Ident(Ident::new("consumes_any", Span::mixed_site())),
+                TokenTree::Group(Group::new(Delimiter::Parenthesis, c.clone()))
+            ]);
+        }
+
+        if let Some(priority) = &self.priority {
+            builder.extend([
+                TokenTree::Punct(Punct::new('.', Spacing::Alone)),
+                TokenTree::Ident(Ident::new("priority", Span::mixed_site())),
+                TokenTree::Group(Group::new(Delimiter::Parenthesis, priority.clone()))
+            ]);
+        }
+
+        if let Some(timeout) = &self.lock_timeout {
+            builder.extend([
+                TokenTree::Punct(Punct::new('.', Spacing::Alone)),
+                TokenTree::Ident(Ident::new("lock_timeout", Span::mixed_site())),
+                TokenTree::Group(Group::new(Delimiter::Parenthesis, timeout.clone()))
+            ]);
+        }
+
+        // async tests must not block the worker threads of the runtime
+        let reserve = match func.is_async {
+            true	=> "reserve_async",
+            false	=> "reserve",
+        };
+
         builder.extend([
             TokenTree::Punct(Punct::new('.', Spacing::Alone)),
-            TokenTree::Ident(Ident::new("reserve", Span::mixed_site())),
+            TokenTree::Ident(Ident::new(reserve, Span::mixed_site())),
             TokenTree::Group(Group::new(Delimiter::Parenthesis, [
                 TokenTree::Punct(Punct::new('&', Spacing::Alone)),
                 TokenTree::Ident(Ident::new(CRATE_NAME, Span::mixed_site())),
@@ -253,16 +469,65 @@ impl Config {
                 TokenTree::Punct(Punct::new('&', Spacing::Joint)),
                 TokenTree::Ident(Ident::new(VARNAME_CURENT_TEST, Span::mixed_site())),
             ].into_iter().collect())),
+        ]);
 
+        if func.is_async {
+            builder.extend([
+                TokenTree::Punct(Punct::new('.', Spacing::Alone)),
+                TokenTree::Ident(Ident::new("await", Span::mixed_site())),
+            ]);
+        }
+
+        // tests are skipped when resources have been poisoned or are not
+        // available; this requires a return value for skipped tests which
+        // is known to exist only for plain tests and ones which can be
+        // skipped already.  Others fail.
+        let can_skip = func.ret.is_none() || self.skip_fn.is_some() || self.skip_result.is_some();
+
+        if !can_skip {
+            builder.extend([
+                TokenTree::Punct(Punct::new('.', Spacing::Alone)),
+                TokenTree::Ident(Ident::new("fail_skipped", Span::mixed_site())),
+                TokenTree::Group(Group::new(Delimiter::Parenthesis, TokenStream::new())),
+            ]);
+        }
+
+        builder.extend([
             TokenTree::Punct(Punct::new(';', Spacing::Joint)),
         ]);
 
+        if !can_skip {
+            return builder.into_iter().collect();
+        }
+
+        // 'if _resource_lock.is_skipped(&etest_current_test) { return ... }'
+        builder.extend([
+            TokenTree::Ident(Ident::new("if", Span::mixed_site())),
+            TokenTree::Ident(Ident::new("_resource_lock", Span::mixed_site())),
+            TokenTree::Punct(Punct::new('.', Spacing::Alone)),
+            TokenTree::Ident(Ident::new("is_skipped", Span::mixed_site())),
+            TokenTree::Group(Group::new(Delimiter::Parenthesis, [
+                TokenTree::Punct(Punct::new('&', Spacing::Joint)),
+                TokenTree::Ident(Ident::new(VARNAME_CURENT_TEST, Span::mixed_site())),
+            ].into_iter().collect())),
+            TokenTree::Group(Group::new(Delimiter::Brace, self.emit_skip_return(func))),
+        ]);
+
         builder.into_iter().collect()
     }
 
     pub fn emit_timeout(self, func: &Function) -> TokenStream {
+        let has_lock = !self.uses.is_empty() || !self.consumes.is_empty() || !self.consumes_any.is_empty();
+
         let Some(timeout) = self.timeout else {
-            return func.body.clone();
+            return match func.is_async && has_lock {
+                // async move { ... }
+                true	=> Self::emit_in_context([
+                    TokenTree::Ident(Ident::new("async", Span::mixed_site())),
+                    TokenTree::Ident(Ident::new("move", Span::mixed_site())),
+                ].into_iter().chain(func.body.clone()).collect()),
+                false	=> func.body.clone(),
+            };
         };
 
         // std::time::Duration::from_millis(&etest_current_test, ..., move || ...)
@@ -300,6 +565,10 @@ impl Config {
             TokenTree::Group(Group::new(Delimiter::Parenthesis, args.into_iter().collect())),
         ];
 
+        if func.is_async && has_lock {
+            return Self::emit_in_context(res.into_iter().collect());
+        }
+
         if func.is_async {
             res.extend([
             TokenTree::Punct(Punct::new('.', Spacing::Alone)),
@@ -309,4 +578,17 @@ impl Config {
 
         res.into_iter().collect()
     }
+
+    /// Emits '_resource_lock.in_context(fut).await'; async tests enter the
+    /// context of their resources only while they are polled
+    fn emit_in_context(fut: TokenStream) -> TokenStream {
+        [
+            TokenTree::Ident(Ident::new("_resource_lock", Span::mixed_site())),
+            TokenTree::Punct(Punct::new('.', Spacing::Alone)),
+            TokenTree::Ident(Ident::new("in_context", Span::mixed_site())),
+            TokenTree::Group(Group::new(Delimiter::Parenthesis, fut)),
+            TokenTree::Punct(Punct::new('.', Spacing::Alone)),
+            TokenTree::Ident(Ident::new("await", Span::mixed_site())),
+        ].into_iter().collect()
+    }
 }
@@ -40,6 +40,16 @@ impl Function {
         }
     }
 
+    /// Tells whether the function has an `#[ignore]` attribute
+    pub fn is_ignored(&self) -> bool {
+        self.attr.iter().any(|a| match a {
+            TokenTree::Group(g) if g.delimiter() == Delimiter::Bracket	=> matches!(
+                g.stream().into_iter().next(),
+                Some(TokenTree::Ident(id)) if id.to_string() == "ignore"),
+            _	=> false,
+        })
+    }
+
     pub fn parse(t: TokenStream) -> Result<Self, Error> {
         // println!("{t}");
 
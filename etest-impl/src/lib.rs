@@ -12,9 +12,11 @@ mod utils;
 mod config;
 mod function;
 mod macros;
+mod resource_id;
 
 use errors::Error;
 use config::Config;
 use function::Function;
 
 pub use macros::etest;
+pub use resource_id::derive_resource_id;
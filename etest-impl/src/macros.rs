@@ -23,7 +23,10 @@ pub fn etest(attr: TokenStream, item: TokenStream) -> TokenStream {
 
     body.extend(cfg.emit_generic(&func));
 
+    // skipped tests are registered but do not wait for other ones
+    body.extend(cfg.emit_register(&func));
     body.extend(cfg.emit_skip_fn(&func));
+    body.extend(cfg.emit_order(&func));
     body.extend(cfg.emit_lock(&func));
     body.extend(cfg.emit_timeout(&func));
 
@@ -0,0 +1,566 @@
+use proc_macro::{Delimiter, Group, Ident, Literal, Punct, Spacing, Span, TokenStream, TokenTree};
+
+use crate::config::ConfigIterator;
+use crate::defs::*;
+use crate::utils::err;
+
+/// Fields of a struct or of an enum variant
+enum Fields {
+    Unit,
+    /// number of fields of a tuple struct or variant
+    Tuple(usize),
+    Named(Vec<Ident>),
+}
+
+/// A struct or an enum variant which is converted into a resource id
+struct Item {
+    /// the path to the item; e.g. `Output::Hdmi`
+    path:	Vec<TokenTree>,
+    /// from the `#[resource(name=...)]` attribute; the name of the item in
+    /// snake case else
+    name:	String,
+    fields:	Fields,
+}
+
+/// A part of the `name` template
+enum Piece {
+    Text(String),
+    /// name of the field and its format spec
+    Field(String, String),
+}
+
+/// Converts `UsbHub` to `usb_hub`; runs of uppercase letters are one word,
+/// e.g. `UsbHDMI` becomes `usb_hdmi` and `HDMIPort` becomes `hdmi_port`
+fn snake_case(name: &str) -> String {
+    let chars: Vec<char> = name.chars().collect();
+    let mut res = String::new();
+
+    for (i, c) in chars.iter().enumerate() {
+        let prev = i.checked_sub(1).map(|p| chars[p]);
+        let next = chars.get(i + 1);
+
+        let starts_word = c.is_uppercase() && match prev {
+            None	=> false,
+            Some(p) if p.is_uppercase()	=> next.is_some_and(|n| n.is_lowercase()),
+            Some(p)	=> p != '_',
+        };
+
+        if starts_word {
+            res.push('_');
+        }
+
+        res.extend(c.to_lowercase());
+    }
+
+    res
+}
+
+/// Returns the content of a plain string literal
+fn string_value(tokens: TokenStream) -> Option<String> {
+    let mut iter = tokens.into_iter();
+
+    let Some(TokenTree::Literal(l)) = iter.next() else {
+        return None;
+    };
+
+    if iter.next().is_some() {
+        return None;
+    }
+
+    let s = l.to_string();
+    let s = s.strip_prefix('"')?.strip_suffix('"')?;
+
+    let mut res = String::new();
+    let mut chars = s.chars();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '\\'	=> match chars.next()? {
+                'n'	=> res.push('\n'),
+                't'	=> res.push('\t'),
+                c @ ('\\' | '"' | '\'')	=> res.push(c),
+                _	=> return None,
+            },
+            c	=> res.push(c),
+        }
+    }
+
+    Some(res)
+}
+
+/// Splits `name` into text and `{field}` placeholders
+fn parse_template(name: &str, span: Span) -> Result<Vec<Piece>, TokenStream> {
+    let mut res = Vec::new();
+    let mut text = String::new();
+    let mut chars = name.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '{' if chars.peek() == Some(&'{')	=> {
+                chars.next();
+                text.push('{');
+            },
+
+            '}' if chars.peek() == Some(&'}')	=> {
+                chars.next();
+                text.push('}');
+            },
+
+            '{'	=> {
+                let mut placeholder = String::new();
+
+                loop {
+                    match chars.next() {
+                        Some('}')	=> break,
+                        Some(c)		=> placeholder.push(c),
+                        None		=> return Err(err(span, "unmatched '{' in name")),
+                    }
+                }
+
+                let (field, spec) = placeholder.split_once(':').unwrap_or((&placeholder, ""));
+
+                if field.is_empty() {
+                    return Err(err(span, "placeholders must name a field"));
+                }
+
+                if !text.is_empty() {
+                    res.push(Piece::Text(std::mem::take(&mut text)));
+                }
+
+                res.push(Piece::Field(field.trim().to_string(), spec.to_string()));
+            },
+
+            '}'	=> return Err(err(span, "unmatched '}' in name")),
+
+            c	=> text.push(c),
+        }
+    }
+
+    if !text.is_empty() {
+        res.push(Piece::Text(text));
+    }
+
+    Ok(res)
+}
+
+/// Consumes the `#[...]` attributes and returns the value of a `name` given
+/// by `#[resource(...)]`
+fn parse_attrs(iter: &mut std::iter::Peekable<impl Iterator<Item = TokenTree>>)
+               -> Result<Option<(String, Span)>, TokenStream> {
+    let mut res = None;
+
+    while matches!(iter.peek(), Some(TokenTree::Punct(p)) if p.as_char() == '#') {
+        iter.next();
+
+        let Some(TokenTree::Group(g)) = iter.next() else {
+            return Err(err(Span::call_site(), "bad attribute"));
+        };
+
+        let mut attr = g.stream().into_iter();
+
+        match (attr.next(), attr.next()) {
+            (Some(TokenTree::Ident(id)), Some(TokenTree::Group(args)))
+                if id.to_string() == "resource"	=> {
+                    for cfg in ConfigIterator::new(args.stream()) {
+                        let cfg = cfg?;
+
+                        match cfg.get_key().as_str() {
+                            "name"	=> {
+                                let val = cfg.convert::<TokenStream>()?.unwrap();
+
+                                match string_value(val) {
+                                    Some(v)	=> res = Some((v, args.span())),
+                                    None	=> return Err(err(args.span(), "'name' must be a string literal")),
+                                }
+                            },
+
+                            c	=> return Err(err(args.span(), &format!("unsupported key: {c:?}"))),
+                        }
+                    }
+                },
+
+            _	=> {},
+        }
+    }
+
+    Ok(res)
+}
+
+/// Consumes `pub`, `pub(crate)` and so on
+fn skip_vis(iter: &mut std::iter::Peekable<impl Iterator<Item = TokenTree>>) {
+    if matches!(iter.peek(), Some(TokenTree::Ident(id)) if id.to_string() == "pub") {
+        iter.next();
+
+        if matches!(iter.peek(), Some(TokenTree::Group(g)) if g.delimiter() == Delimiter::Parenthesis) {
+            iter.next();
+        }
+    }
+}
+
+/// Consumes tokens (e.g. a type) until the next ',' outside of `<...>`
+fn skip_until_comma(iter: &mut std::iter::Peekable<impl Iterator<Item = TokenTree>>) {
+    let mut depth = 0_usize;
+    let mut prev_minus = false;
+
+    for t in iter.by_ref() {
+        match &t {
+            TokenTree::Punct(p) if p.as_char() == ',' && depth == 0	=> break,
+            TokenTree::Punct(p) if p.as_char() == '<'	=> depth += 1,
+            // '->' of function types
+            TokenTree::Punct(p) if p.as_char() == '>' && !prev_minus	=> depth = depth.saturating_sub(1),
+            _	=> {},
+        }
+
+        prev_minus = matches!(&t, TokenTree::Punct(p) if p.as_char() == '-');
+    }
+}
+
+fn parse_fields(group: Option<&Group>) -> Result<Fields, TokenStream> {
+    let Some(group) = group else {
+        return Ok(Fields::Unit);
+    };
+
+    let named = match group.delimiter() {
+        Delimiter::Brace	=> true,
+        Delimiter::Parenthesis	=> false,
+        _			=> return Err(err(group.span(), "unexpected fields")),
+    };
+
+    let mut iter = group.stream().into_iter().peekable();
+    let mut names = Vec::new();
+    let mut cnt = 0;
+
+    loop {
+        parse_attrs(&mut iter)?;
+        skip_vis(&mut iter);
+
+        if iter.peek().is_none() {
+            break;
+        }
+
+        if named {
+            match iter.next() {
+                Some(TokenTree::Ident(id))	=> names.push(id),
+                _	=> return Err(err(group.span(), "expected field name")),
+            }
+        }
+
+        skip_until_comma(&mut iter);
+        cnt += 1;
+    }
+
+    Ok(match named {
+        true	=> Fields::Named(names),
+        false	=> Fields::Tuple(cnt),
+    })
+}
+
+fn path_sep() -> [TokenTree; 2] {
+    [
+        TokenTree::Punct(Punct::new(':', Spacing::Joint)),
+        TokenTree::Punct(Punct::new(':', Spacing::Alone)),
+    ]
+}
+
+/// Returns the name of a field without the `r#` of raw identifiers
+fn field_name(id: &Ident) -> String {
+    let name = id.to_string();
+
+    name.strip_prefix("r#").map(String::from).unwrap_or(name)
+}
+
+fn binding(field: &str) -> Ident {
+    Ident::new(&format!("etest_field_{field}"), Span::mixed_site())
+}
+
+impl Item {
+    /// Emits `<path> => <expr>` for the `match` in `from()`
+    fn emit_arm(&self, span: Span) -> Result<TokenStream, TokenStream> {
+        let fields: Vec<String> = match &self.fields {
+            Fields::Unit		=> Vec::new(),
+            Fields::Tuple(cnt)	=> (0..*cnt).map(|i| i.to_string()).collect(),
+            Fields::Named(names)	=> names.iter().map(field_name).collect(),
+        };
+
+        let mut pieces = parse_template(&self.name, span)?;
+
+        // without placeholders, the fields are appended as components of a
+        // hierarchical id
+        if !pieces.iter().any(|p| matches!(p, Piece::Field(..))) {
+            for f in &fields {
+                pieces.push(Piece::Text("/".to_string()));
+                pieces.push(Piece::Field(f.clone(), String::new()));
+            }
+        }
+
+        let mut fmt = String::new();
+        let mut args = Vec::new();
+
+        for p in pieces {
+            match p {
+                Piece::Text(t)	=> fmt.push_str(&t.replace('{', "{{").replace('}', "}}")),
+                Piece::Field(f, spec)	=> {
+                    if !fields.contains(&f) {
+                        return Err(err(span, &format!("unknown field {f:?} in name")));
+                    }
+
+                    match spec.is_empty() {
+                        true	=> fmt.push_str("{}"),
+                        false	=> fmt.push_str(&format!("{{:{spec}}}")),
+                    }
+
+                    args.extend([
+                        TokenTree::Punct(Punct::new(',', Spacing::Alone)),
+                        TokenTree::Ident(binding(&f)),
+                    ]);
+                },
+            }
+        }
+
+        let mut res = self.path.clone();
+
+        match &self.fields {
+            Fields::Unit		=> {},
+            Fields::Tuple(_)	=> {
+                let mut pat = Vec::new();
+
+                for f in &fields {
+                    pat.extend([
+                        TokenTree::Ident(binding(f)),
+                        TokenTree::Punct(Punct::new(',', Spacing::Alone)),
+                    ]);
+                }
+
+                res.push(TokenTree::Group(Group::new(Delimiter::Parenthesis, pat.into_iter().collect())));
+            },
+            Fields::Named(names)	=> {
+                let mut pat = Vec::new();
+
+                for n in names {
+                    pat.extend([
+                        TokenTree::Ident(n.clone()),
+                        TokenTree::Punct(Punct::new(':', Spacing::Alone)),
+                        TokenTree::Ident(binding(&field_name(n))),
+                        TokenTree::Punct(Punct::new(',', Spacing::Alone)),
+                    ]);
+                }
+
+                res.push(TokenTree::Group(Group::new(Delimiter::Brace, pat.into_iter().collect())));
+            },
+        }
+
+        res.extend([
+            TokenTree::Punct(Punct::new('=', Spacing::Joint)),
+            TokenTree::Punct(Punct::new('>', Spacing::Alone)),
+            TokenTree::Ident(Ident::new(CRATE_NAME, Span::mixed_site())),
+        ]);
+        res.extend(path_sep());
+        res.push(TokenTree::Ident(Ident::new("ResourceId", Span::mixed_site())));
+        res.extend(path_sep());
+
+        if args.is_empty() {
+            // 'etest::ResourceId::new("hdmi")'
+            res.extend([
+                TokenTree::Ident(Ident::new("new", Span::mixed_site())),
+                TokenTree::Group(Group::new(Delimiter::Parenthesis, [
+                    TokenTree::Literal(Literal::string(&fmt.replace("{{", "{").replace("}}", "}"))),
+                ].into_iter().collect())),
+            ]);
+        } else {
+            // 'etest::ResourceId::from_string(::std::format!("port/{}", etest_field_0))'
+            let mut format = vec![TokenTree::Literal(Literal::string(&fmt))];
+
+            format.extend(args);
+
+            res.extend([
+                TokenTree::Ident(Ident::new("from_string", Span::mixed_site())),
+                TokenTree::Group(Group::new(Delimiter::Parenthesis, [
+                    TokenTree::Punct(Punct::new(':', Spacing::Joint)),
+                    TokenTree::Punct(Punct::new(':', Spacing::Alone)),
+                    TokenTree::Ident(Ident::new("std", Span::mixed_site())),
+                    TokenTree::Punct(Punct::new(':', Spacing::Joint)),
+                    TokenTree::Punct(Punct::new(':', Spacing::Alone)),
+                    TokenTree::Ident(Ident::new("format", Span::mixed_site())),
+                    TokenTree::Punct(Punct::new('!', Spacing::Alone)),
+                    TokenTree::Group(Group::new(Delimiter::Parenthesis, format.into_iter().collect())),
+                ].into_iter().collect())),
+            ]);
+        }
+
+        res.push(TokenTree::Punct(Punct::new(',', Spacing::Alone)));
+
+        Ok(res.into_iter().collect())
+    }
+}
+
+fn parse(item: TokenStream) -> Result<(Ident, Vec<Item>), TokenStream> {
+    let mut iter = item.into_iter().peekable();
+
+    let name = parse_attrs(&mut iter)?;
+    skip_vis(&mut iter);
+
+    let is_enum = match iter.next() {
+        Some(TokenTree::Ident(id)) if id.to_string() == "enum"		=> true,
+        Some(TokenTree::Ident(id)) if id.to_string() == "struct"	=> false,
+        _	=> return Err(err(Span::call_site(), "ResourceId can be derived only for enums and structs")),
+    };
+
+    let Some(TokenTree::Ident(ty)) = iter.next() else {
+        return Err(err(Span::call_site(), "missing type name"));
+    };
+
+    let body = match iter.next() {
+        Some(TokenTree::Group(g))	=> Some(g),
+        Some(TokenTree::Punct(p)) if p.as_char() == ';'	=> None,
+        Some(TokenTree::Punct(p)) if p.as_char() == '<'	=>
+            return Err(err(p.span(), "ResourceId can not be derived for generic types")),
+        _	=> return Err(err(ty.span(), "unsupported type declaration")),
+    };
+
+    if !is_enum {
+        let item = Item {
+            path:	vec![TokenTree::Ident(ty.clone())],
+            name:	name.map(|(n, _)| n).unwrap_or_else(|| snake_case(&ty.to_string())),
+            fields:	parse_fields(body.as_ref())?,
+        };
+
+        return Ok((ty, vec![item]));
+    }
+
+    if let Some((_, span)) = name {
+        return Err(err(span, "'name' must be given on the variants of an enum"));
+    }
+
+    let Some(body) = body else {
+        return Err(err(ty.span(), "missing variants"));
+    };
+
+    let mut iter = body.stream().into_iter().peekable();
+    let mut res = Vec::new();
+
+    loop {
+        let name = parse_attrs(&mut iter)?;
+
+        let variant = match iter.next() {
+            None				=> break,
+            Some(TokenTree::Ident(id))	=> id,
+            Some(t)				=> return Err(err(t.span(), "expected variant")),
+        };
+
+        let fields = match iter.peek() {
+            Some(TokenTree::Group(g))	=> {
+                let f = parse_fields(Some(g))?;
+
+                iter.next();
+                f
+            },
+            _	=> Fields::Unit,
+        };
+
+        // '= <discriminant>' and ','
+        skip_until_comma(&mut iter);
+
+        let mut path = vec![TokenTree::Ident(ty.clone())];
+
+        path.extend(path_sep());
+        path.push(TokenTree::Ident(variant.clone()));
+
+        res.push(Item {
+            path:	path,
+            name:	name.map(|(n, _)| n).unwrap_or_else(|| snake_case(&variant.to_string())),
+            fields:	fields,
+        });
+    }
+
+    Ok((ty, res))
+}
+
+/// Turns the `compile_error!()` of `e` into an item
+fn item_err(mut e: TokenStream) -> TokenStream {
+    e.extend([TokenTree::Punct(Punct::new(';', Spacing::Alone))]);
+    e
+}
+
+/// Implements `From<T> for etest::ResourceId`
+///
+/// # Example
+///
+/// ```ignore
+/// #[derive(ResourceId)]
+/// enum Usb {
+///     Hub(u8),
+///     #[resource(name = "usb/hub{hub}/port{port}")]
+///     Port { hub: u8, port: u8 },
+/// }
+/// ```
+///
+/// expands to
+///
+/// ```ignore
+/// impl ::core::convert::From<Usb> for etest::ResourceId {
+///     fn from(v: Usb) -> Self {
+///         match v {
+///             Usb::Hub(etest_field_0,) => etest::ResourceId::from_string(::std::format!("hub/{}", etest_field_0)),
+///             Usb::Port { hub: etest_field_hub, port: etest_field_port, } =>
+///                 etest::ResourceId::from_string(::std::format!("usb/hub{}/port{}", etest_field_hub, etest_field_port)),
+///         }
+///     }
+/// }
+/// ```
+pub fn derive_resource_id(item: TokenStream) -> TokenStream {
+    let (ty, items) = match parse(item) {
+        Ok(r)	=> r,
+        Err(e)	=> return item_err(e),
+    };
+
+    let mut arms = Vec::new();
+
+    for i in &items {
+        match i.emit_arm(ty.span()) {
+            Ok(a)	=> arms.extend(a),
+            Err(e)	=> return item_err(e),
+        }
+    }
+
+    let value = Ident::new("value", Span::mixed_site());
+
+    let mut res = vec![
+        TokenTree::Ident(Ident::new("impl", Span::mixed_site())),
+    ];
+
+    res.extend(path_sep());
+    res.push(TokenTree::Ident(Ident::new("core", Span::mixed_site())));
+    res.extend(path_sep());
+    res.push(TokenTree::Ident(Ident::new("convert", Span::mixed_site())));
+    res.extend(path_sep());
+    res.extend([
+        TokenTree::Ident(Ident::new("From", Span::mixed_site())),
+        TokenTree::Punct(Punct::new('<', Spacing::Alone)),
+        TokenTree::Ident(ty.clone()),
+        TokenTree::Punct(Punct::new('>', Spacing::Alone)),
+        TokenTree::Ident(Ident::new("for", Span::mixed_site())),
+        TokenTree::Ident(Ident::new(CRATE_NAME, Span::mixed_site())),
+    ]);
+    res.extend(path_sep());
+    res.push(TokenTree::Ident(Ident::new("ResourceId", Span::mixed_site())));
+
+    res.push(TokenTree::Group(Group::new(Delimiter::Brace, [
+        TokenTree::Ident(Ident::new("fn", Span::mixed_site())),
+        TokenTree::Ident(Ident::new("from", Span::mixed_site())),
+        TokenTree::Group(Group::new(Delimiter::Parenthesis, [
+            TokenTree::Ident(value.clone()),
+            TokenTree::Punct(Punct::new(':', Spacing::Alone)),
+            TokenTree::Ident(ty.clone()),
+        ].into_iter().collect())),
+        TokenTree::Punct(Punct::new('-', Spacing::Joint)),
+        TokenTree::Punct(Punct::new('>', Spacing::Alone)),
+        TokenTree::Ident(Ident::new("Self", Span::mixed_site())),
+        TokenTree::Group(Group::new(Delimiter::Brace, [
+            TokenTree::Ident(Ident::new("match", Span::mixed_site())),
+            TokenTree::Ident(value),
+            TokenTree::Group(Group::new(Delimiter::Brace, arms.into_iter().collect())),
+        ].into_iter().collect())),
+    ].into_iter().collect())));
+
+    res.into_iter().collect()
+}
@@ -0,0 +1,167 @@
+//! Tests async reservation of resources
+
+use std::future::Future;
+use std::pin::pin;
+use std::sync::Arc;
+use std::sync::atomic::{ AtomicBool, Ordering };
+use std::sync::mpsc::{ channel, Sender };
+use std::task::{ Context, Poll, Wake, Waker };
+use std::thread::Thread;
+use std::time::{ Duration, Instant };
+
+use etest::prelude::*;
+
+struct Flag(AtomicBool);
+
+impl Wake for Flag {
+    fn wake(self: Arc<Self>) {
+        self.0.store(true, Ordering::SeqCst);
+    }
+}
+
+struct Unpark(Thread);
+
+impl Wake for Unpark {
+    fn wake(self: Arc<Self>) {
+        self.0.unpark();
+    }
+}
+
+/// Minimal executor for running a future on the current thread
+fn block_on<F: Future>(f: F) -> F::Output {
+    let mut f = pin!(f);
+    let waker = Waker::from(Arc::new(Unpark(std::thread::current())));
+    let mut cx = Context::from_waker(&waker);
+
+    loop {
+        match f.as_mut().poll(&mut cx) {
+            Poll::Ready(v)	=> break v,
+            Poll::Pending	=> std::thread::park(),
+        }
+    }
+}
+
+/// Consumes `res` in another thread until the returned sender is dropped
+fn hold(res: &'static str) -> Sender<()> {
+    let (locked_tx, locked_rx) = channel();
+    let (release_tx, release_rx) = channel::<()>();
+
+    std::thread::spawn(move || {
+        let _guard = etest::reserve().consumes(res).lock();
+
+        locked_tx.send(()).unwrap();
+        let _ = release_rx.recv();
+    });
+
+    locked_rx.recv().unwrap();
+
+    release_tx
+}
+
+#[test]
+fn test_0() {
+    let release = hold("async-A");
+
+    let flag = Arc::new(Flag(AtomicBool::new(false)));
+    let waker = Waker::from(flag.clone());
+    let mut cx = Context::from_waker(&waker);
+
+    let mut fut = pin!(etest::reserve().consumes("async-A").lock_async());
+
+    // thread is not blocked while the resource is held by another test
+    assert!(fut.as_mut().poll(&mut cx).is_pending());
+    assert!(!flag.0.load(Ordering::SeqCst));
+
+    drop(release);
+
+    let start = Instant::now();
+
+    while !flag.0.load(Ordering::SeqCst) {
+        assert!(start.elapsed() < Duration::from_secs(2));
+        std::thread::sleep(Duration::from_millis(10));
+    }
+
+    assert!(fut.as_mut().poll(&mut cx).is_ready());
+}
+
+// cancelled reservations are removed from the queue
+#[test]
+fn test_1() {
+    let release = hold("async-B");
+
+    let waker = Waker::from(Arc::new(Flag(AtomicBool::new(false))));
+    let mut cx = Context::from_waker(&waker);
+    let is_waiting = || etest::resources::snapshot().waiters.iter()
+        .any(|w| w.consumes.contains(&"async-B".into()));
+
+    {
+        let mut fut = pin!(etest::reserve().consumes("async-B").lock_async());
+
+        assert!(fut.as_mut().poll(&mut cx).is_pending());
+        assert!(is_waiting());
+    }
+
+    assert!(!is_waiting());
+
+    drop(release);
+}
+
+#[etest(consumes="async-C", test_fn=())]
+async fn test_inner_2() -> u32 {
+    42
+}
+
+#[test]
+fn test_outer_2() {
+    let release = hold("async-C");
+
+    std::thread::spawn(move || {
+        std::thread::sleep(Duration::from_millis(200));
+        drop(release);
+    });
+
+    assert_eq!(block_on(test_inner_2()), 42);
+}
+
+#[should_panic]
+#[test]
+fn test_3() {
+    let _release = hold("async-D");
+
+    block_on(etest::reserve()
+             .consumes("async-D")
+             .lock_timeout(200)
+             .lock_async());
+}
+
+// independent futures which are polled by the same thread do not share
+// their reservations
+#[test]
+fn test_4() {
+    let waker = Waker::from(Arc::new(Flag(AtomicBool::new(false))));
+    let mut cx = Context::from_waker(&waker);
+
+    let mut fut_a = pin!(etest::reserve().consumes("async-E").lock_async());
+    let mut fut_b = pin!(etest::reserve().consumes("async-E").lock_async());
+
+    let Poll::Ready(guard_a) = fut_a.as_mut().poll(&mut cx) else {
+        panic!("resource not granted");
+    };
+
+    assert!(fut_b.as_mut().poll(&mut cx).is_pending());
+
+    // the future did not enter its context in this thread; other
+    // reservations of the thread do not share its resources
+    let res = std::panic::catch_unwind(|| etest::reserve()
+                                       .consumes("async-E")
+                                       .lock_timeout(100)
+                                       .lock());
+
+    assert!(res.is_err());
+    assert!(fut_b.as_mut().poll(&mut cx).is_pending());
+
+    // the guard can be released by another thread
+    std::thread::spawn(move || drop(guard_a)).join().unwrap();
+
+    assert!(block_on(fut_b).selected().is_none());
+}
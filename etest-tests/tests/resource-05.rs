@@ -24,7 +24,6 @@ fn test_outer_0_0() {
 fn test_inner_0_1() {
 }
 
-#[should_panic]
 #[etest(timeout=2_000, consumes=[get_resource(1)])]
 fn test_outer_0_1() {
     test_inner_0_1();
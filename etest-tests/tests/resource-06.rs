@@ -0,0 +1,59 @@
+//! Tests resources with a capacity
+
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+use std::thread::sleep;
+
+use etest::prelude::*;
+
+static RES_SERIAL:	AtomicU64 = AtomicU64::new(0);
+
+const SERIAL: Slots<&str> = Slots("serial", 2);
+
+fn consume_serial() {
+    assert!(RES_SERIAL.fetch_add(1, Ordering::Relaxed) < 2);
+
+    sleep(Duration::from_millis(500));
+
+    RES_SERIAL.fetch_sub(1, Ordering::Relaxed);
+}
+
+#[etest(consumes=[Slots("serial", 2)])]
+fn test_0() {
+    consume_serial();
+}
+
+#[etest(consumes=[Slots("serial", 2)])]
+fn test_1() {
+    consume_serial();
+}
+
+#[etest(consumes=[SERIAL])]
+fn test_2() {
+    consume_serial();
+}
+
+#[etest(consumes=[SERIAL])]
+fn test_3() {
+    consume_serial();
+}
+
+#[etest(uses=["serial"])]
+fn test_4() {
+    assert_eq!(RES_SERIAL.load(Ordering::Relaxed), 0);
+
+    sleep(Duration::from_millis(500));
+
+    assert_eq!(RES_SERIAL.load(Ordering::Relaxed), 0);
+}
+
+
+#[etest(consumes=[Slots("pool", 1)], test_fn=())]
+fn test_inner_5() {
+}
+
+#[should_panic]
+#[etest(consumes=[Slots("pool", 2)])]
+fn test_outer_5() {
+    test_inner_5();
+}
@@ -0,0 +1,65 @@
+//! Tests weighted resource usage
+
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+use std::thread::sleep;
+
+use etest::prelude::*;
+
+static RES_MEMORY:	AtomicU64 = AtomicU64::new(0);
+
+const MEMORY: Slots<&str> = Slots("memory", 16);
+
+fn use_memory(units: u64) {
+    assert!(RES_MEMORY.fetch_add(units, Ordering::Relaxed) + units <= 16);
+
+    sleep(Duration::from_millis(500));
+
+    RES_MEMORY.fetch_sub(units, Ordering::Relaxed);
+}
+
+#[etest(uses=[Weight(MEMORY, 4)])]
+fn test_0() {
+    use_memory(4);
+}
+
+#[etest(uses=[Weight(MEMORY, 4)])]
+fn test_1() {
+    use_memory(4);
+}
+
+#[etest(uses=[Weight(MEMORY, 8)])]
+fn test_2() {
+    use_memory(8);
+}
+
+#[etest(uses=[Weight(MEMORY, 12)])]
+fn test_3() {
+    use_memory(12);
+}
+
+#[etest(uses=[Weight(Slots("memory", 16), 16)])]
+fn test_4() {
+    use_memory(16);
+}
+
+#[etest(uses=["memory"])]
+fn test_5() {
+    use_memory(0);
+}
+
+#[etest(consumes=["memory"])]
+fn test_6() {
+    assert_eq!(RES_MEMORY.load(Ordering::Relaxed), 0);
+}
+
+
+#[should_panic]
+#[etest(uses=[Weight(MEMORY, 17)])]
+fn test_7() {
+}
+
+#[should_panic]
+#[etest(consumes=[Weight("not-counted", 2)])]
+fn test_8() {
+}
@@ -0,0 +1,80 @@
+//! Tests detection of deadlocks
+
+use std::sync::{ Arc, Barrier };
+
+use etest::prelude::*;
+
+#[etest(consumes="A", test_fn=())]
+fn test_inner_0() {
+}
+
+// there is no timeout; test would hang forever without deadlock detection
+#[should_panic(expected = "DEADLOCK")]
+#[etest(uses="A")]
+fn test_outer_0() {
+    test_inner_0();
+}
+
+
+#[etest(consumes=[Weight(Slots("B", 2), 2)], test_fn=())]
+fn test_inner_1() {
+}
+
+#[should_panic(expected = "DEADLOCK")]
+#[etest(timeout=5_000, consumes=[Slots("B", 2)])]
+fn test_outer_1() {
+    test_inner_1();
+}
+
+
+#[etest(consumes=[Slots("C", 2)], test_fn=())]
+fn test_inner_2() {
+}
+
+// the second slot is free; this is not a deadlock
+#[etest(consumes=[Slots("C", 2)])]
+fn test_outer_2() {
+    test_inner_2();
+}
+
+
+#[etest(consumes="X", no_default_uses, test_fn=())]
+fn test_cycle_x(barrier: Arc<Barrier>) {
+    barrier.wait();
+    test_cycle_inner_y();
+}
+
+#[etest(consumes="Y", no_default_uses, test_fn=())]
+fn test_cycle_y(barrier: Arc<Barrier>) {
+    barrier.wait();
+    test_cycle_inner_x();
+}
+
+#[etest(consumes="X", no_default_uses, test_fn=())]
+fn test_cycle_inner_x() {
+}
+
+#[etest(consumes="Y", no_default_uses, test_fn=())]
+fn test_cycle_inner_y() {
+}
+
+// two tests waiting for each other's resources; exactly one of them must
+// detect the deadlock
+#[etest(no_default_uses)]
+fn test_cycle() {
+    let barrier = Arc::new(Barrier::new(2));
+
+    let t_x = std::thread::spawn({
+        let barrier = barrier.clone();
+        move || test_cycle_x(barrier)
+    });
+
+    let t_y = std::thread::spawn({
+        let barrier = barrier.clone();
+        move || test_cycle_y(barrier)
+    });
+
+    let res = [t_x.join(), t_y.join()];
+
+    assert_eq!(res.iter().filter(|r| r.is_err()).count(), 1);
+}
@@ -0,0 +1,107 @@
+//! Tests nested reservations of resources which are held by the calling
+//! test already
+
+use std::sync::Arc;
+use std::sync::atomic::{ AtomicBool, Ordering };
+use std::time::Duration;
+use std::thread::sleep;
+
+use etest::prelude::*;
+
+#[etest(uses="A", test_fn=())]
+fn test_inner_inner_0() {
+}
+
+#[etest(consumes="A", test_fn=())]
+fn test_inner_0() {
+    test_inner_inner_0();
+}
+
+#[etest(consumes="A", no_default_uses, test_fn=())]
+fn test_other_0(done: Arc<AtomicBool>) {
+    done.store(true, Ordering::Relaxed);
+}
+
+#[etest(consumes="A")]
+fn test_outer_0() {
+    test_inner_0();
+    test_inner_0();
+
+    // resource must be still held after the nested reservations have been
+    // released
+    let done = Arc::new(AtomicBool::new(false));
+
+    std::thread::spawn({
+        let done = done.clone();
+        move || test_other_0(done)
+    });
+
+    sleep(Duration::from_millis(500));
+
+    assert!(!done.load(Ordering::Relaxed));
+}
+
+// the nested reservation happens in the worker thread of the timeout
+#[etest(timeout=2_000, consumes="A")]
+fn test_outer_1() {
+    test_inner_0();
+}
+
+#[etest(notparallel, test_fn=())]
+fn test_inner_2() {
+}
+
+#[etest(notparallel)]
+fn test_outer_2() {
+    test_inner_2();
+}
+
+#[etest(uses=[Weight(Slots("B", 4), 1)], test_fn=())]
+fn test_inner_3() {
+}
+
+#[etest(uses=[Weight(Slots("B", 4), 2)])]
+fn test_outer_3() {
+    test_inner_3();
+}
+
+// a guard which is dropped by another thread leaves the context of the
+// reservation in the thread which made it
+#[test]
+fn test_4() {
+    let guard = etest::reserve().consumes("C").lock();
+
+    std::thread::spawn(move || drop(guard)).join().unwrap();
+
+    let (user, release) = std::sync::mpsc::channel::<()>();
+    let user_thread = std::thread::spawn(move || {
+        let _guard = etest::reserve().uses("D").lock();
+
+        let _ = release.recv();
+    });
+
+    while !etest::resources::snapshot().resources.iter().any(|r| r.id == "D".into() && !r.users.is_empty()) {
+        sleep(Duration::from_millis(10));
+    }
+
+    // queued behind the user
+    let consumer = std::thread::spawn(|| {
+        let _guard = etest::reserve().consumes("D").lock();
+    });
+
+    while !etest::resources::snapshot().waiters.iter().any(|w| w.consumes.contains(&"D".into())) {
+        sleep(Duration::from_millis(10));
+    }
+
+    // not a nested reservation; it must not overtake the waiting consumer
+    let res = std::panic::catch_unwind(|| etest::reserve()
+                                       .uses("D")
+                                       .lock_timeout(200)
+                                       .lock());
+
+    assert!(res.is_err());
+
+    drop(user);
+    user_thread.join().unwrap();
+    consumer.join().unwrap();
+}
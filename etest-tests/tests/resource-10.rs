@@ -0,0 +1,55 @@
+//! Tests the 'lock_timeout' parameter
+
+use std::sync::mpsc::{ channel, Sender };
+use std::time::Duration;
+use std::thread::sleep;
+
+use etest::prelude::*;
+
+#[etest(consumes=[res], no_default_uses, test_fn=())]
+fn hold(res: &'static str, tx: Sender<()>, d: Duration) {
+    tx.send(()).unwrap();
+    sleep(d);
+}
+
+#[etest(consumes=[res], no_default_uses, lock_timeout=200, test_fn=())]
+fn wait(res: &'static str) {
+}
+
+fn run_with_holder(res: &'static str, d: Duration) -> std::thread::Result<()> {
+    let (tx, rx) = channel();
+
+    let holder = std::thread::spawn(move || hold(res, tx, d));
+
+    rx.recv().unwrap();
+
+    let res = std::panic::catch_unwind(|| wait(res));
+
+    holder.join().unwrap();
+
+    res
+}
+
+#[etest(no_default_uses)]
+fn test_0() {
+    let err = run_with_holder("A0", Duration::from_millis(1_000)).unwrap_err();
+    let msg = err.downcast_ref::<String>().unwrap();
+
+    assert!(msg.contains("TIMEOUT"), "{msg}");
+    assert!(msg.contains("Id(\"A0\") owned by [etest-tests/tests/resource-10.rs:9:1"), "{msg}");
+}
+
+#[etest(no_default_uses)]
+fn test_1() {
+    run_with_holder("A1", Duration::from_millis(50)).unwrap();
+}
+
+#[etest(consumes="B", test_fn=())]
+fn test_inner_2() {
+}
+
+// nested reservations are not affected by the timeout
+#[etest(consumes="B", lock_timeout=0)]
+fn test_outer_2() {
+    test_inner_2();
+}
@@ -0,0 +1,71 @@
+//! Tests that resources are granted in the order of the requests
+
+use std::sync::{ Arc, Mutex };
+use std::time::Duration;
+use std::thread::{ sleep, spawn };
+
+use etest::prelude::*;
+
+type Log = Arc<Mutex<Vec<&'static str>>>;
+
+#[etest(uses=[res], no_default_uses, test_fn=())]
+fn use_res(res: &'static str, log: Log, name: &'static str) {
+    log.lock().unwrap().push(name);
+    sleep(Duration::from_millis(300));
+}
+
+#[etest(consumes=[res], no_default_uses, test_fn=())]
+fn consume_res(res: &'static str, log: Log, name: &'static str) {
+    log.lock().unwrap().push(name);
+    sleep(Duration::from_millis(300));
+}
+
+fn run(log: &Log, delay: u64, f: fn(&'static str, Log, &'static str),
+       res: &'static str, name: &'static str) -> std::thread::JoinHandle<()> {
+    let log = log.clone();
+    let t = spawn(move || f(res, log, name));
+
+    sleep(Duration::from_millis(delay));
+
+    t
+}
+
+// a test which consumes a resource is not starved by ones which use it
+#[etest(no_default_uses)]
+fn test_0() {
+    let log = Log::default();
+
+    let threads = [
+        run(&log, 100, use_res, "F0", "use-0"),
+        run(&log, 100, consume_res, "F0", "consume-0"),
+        run(&log, 100, use_res, "F0", "use-1"),
+        run(&log,   0, use_res, "F0", "use-2"),
+    ];
+
+    for t in threads {
+        t.join().unwrap();
+    }
+
+    let log = log.lock().unwrap();
+
+    assert_eq!(log[..2], ["use-0", "consume-0"]);
+}
+
+// consumers are served in order too
+#[etest(no_default_uses)]
+fn test_1() {
+    let log = Log::default();
+
+    let threads = [
+        run(&log, 100, consume_res, "F1", "consume-0"),
+        run(&log, 100, consume_res, "F1", "consume-1"),
+        run(&log, 100, use_res, "F1", "use-0"),
+        run(&log, 100, consume_res, "F1", "consume-2"),
+    ];
+
+    for t in threads {
+        t.join().unwrap();
+    }
+
+    assert_eq!(*log.lock().unwrap(), ["consume-0", "consume-1", "use-0", "consume-2"]);
+}
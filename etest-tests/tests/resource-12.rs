@@ -0,0 +1,59 @@
+//! Tests the 'priority' parameter
+
+use std::sync::{ Arc, Mutex };
+use std::time::Duration;
+use std::thread::{ sleep, spawn };
+
+use etest::prelude::*;
+
+type Log = Arc<Mutex<Vec<&'static str>>>;
+
+#[etest(consumes="P", no_default_uses, priority=prio, test_fn=())]
+fn consume_p(log: Log, name: &'static str, prio: i32) {
+    log.lock().unwrap().push(name);
+    sleep(Duration::from_millis(400));
+}
+
+#[etest(uses="P", no_default_uses, priority=prio, test_fn=())]
+fn use_p(log: Log, name: &'static str, prio: i32) {
+    log.lock().unwrap().push(name);
+    sleep(Duration::from_millis(400));
+}
+
+#[etest(no_default_uses)]
+fn test_0() {
+    let log = Log::default();
+    let mut threads = Vec::new();
+
+    for (f, name, prio) in [
+        (consume_p as fn(_, _, _), "first", 0),
+        (consume_p, "low", -1),
+        (use_p, "normal-0", 0),
+        (use_p, "normal-1", 0),
+        (consume_p, "high", 10),
+    ] {
+        let log = log.clone();
+
+        threads.push(spawn(move || f(log, name, prio)));
+        sleep(Duration::from_millis(50));
+    }
+
+    for t in threads {
+        t.join().unwrap();
+    }
+
+    let mut log = log.lock().unwrap();
+
+    // the users run in parallel
+    log[2..4].sort();
+
+    assert_eq!(log[..], ["first", "high", "normal-0", "normal-1", "low"]);
+}
+
+#[etest(priority=23)]
+fn test_1() {
+}
+
+#[etest(priority=-1, notparallel)]
+fn test_2() {
+}
@@ -0,0 +1,101 @@
+//! Tests locking of resources across processes
+#![cfg(feature = "flock")]
+
+use std::process::{ Child, Command, Stdio };
+use std::time::{ Duration, SystemTime };
+use std::thread::sleep;
+
+use etest::prelude::*;
+
+fn now_ms() -> u128 {
+    SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .unwrap()
+        .as_millis()
+}
+
+/// Marks the time while the resource is held by the child process
+fn hold() {
+    println!("START {}", now_ms());
+    sleep(Duration::from_millis(500));
+    println!("END {}", now_ms());
+}
+
+/// Runs the ignored test `name` of this binary in a new process
+fn spawn(name: &str) -> Child {
+    Command::new(std::env::current_exe().unwrap())
+        .args(["--exact", name, "--ignored", "--nocapture", "--test-threads=1"])
+        .stdout(Stdio::piped())
+        .spawn()
+        .unwrap()
+}
+
+/// Waits for the child and returns the interval in which it held the
+/// resource
+fn interval(child: Child) -> (u128, u128) {
+    let output = child.wait_with_output().unwrap();
+
+    assert!(output.status.success());
+
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    let value = |tag: &str| stdout.lines()
+        // the harness prints the test name on the same line
+        .find_map(|l| l.split_once(tag).map(|(_, v)| v))
+        .unwrap()
+        .parse::<u128>()
+        .unwrap();
+
+    (value("START "), value("END "))
+}
+
+fn overlaps(a: (u128, u128), b: (u128, u128)) -> bool {
+    a.0 < b.1 && b.0 < a.1
+}
+
+#[ignore]
+#[etest(consumes=["etest-tests/resource-13/X"], no_default_uses)]
+fn child_consume_x() {
+    hold();
+}
+
+#[ignore]
+#[etest(uses=["etest-tests/resource-13/Y"], no_default_uses)]
+fn child_use_y() {
+    hold();
+}
+
+#[ignore]
+#[etest(uses=["etest-tests/resource-13/Z"], no_default_uses)]
+fn child_use_z() {
+    hold();
+}
+
+#[ignore]
+#[etest(consumes=["etest-tests/resource-13/Z"], no_default_uses)]
+fn child_consume_z() {
+    hold();
+}
+
+#[test]
+fn test_0() {
+    let a = spawn("child_consume_x");
+    let b = spawn("child_consume_x");
+
+    assert!(!overlaps(interval(a), interval(b)));
+}
+
+#[test]
+fn test_1() {
+    let a = spawn("child_use_y");
+    let b = spawn("child_use_y");
+
+    assert!(overlaps(interval(a), interval(b)));
+}
+
+#[test]
+fn test_2() {
+    let a = spawn("child_use_z");
+    let b = spawn("child_consume_z");
+
+    assert!(!overlaps(interval(a), interval(b)));
+}
@@ -0,0 +1,71 @@
+//! Tests hierarchical resources
+
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+use std::thread::sleep;
+
+use etest::prelude::*;
+
+static RES_LAB:		AtomicU64 = AtomicU64::new(0);
+static RES_HUB:		AtomicU64 = AtomicU64::new(0);
+static RES_PORT:	AtomicU64 = AtomicU64::new(0);
+
+fn hold(res: &AtomicU64) {
+    res.fetch_add(1, Ordering::Relaxed);
+    sleep(Duration::from_millis(300));
+    res.fetch_sub(1, Ordering::Relaxed);
+}
+
+#[etest(consumes="lab")]
+fn test_0() {
+    assert_eq!(RES_HUB.load(Ordering::Relaxed), 0);
+    assert_eq!(RES_PORT.load(Ordering::Relaxed), 0);
+
+    hold(&RES_LAB);
+}
+
+#[etest(consumes="lab/hub1")]
+fn test_1() {
+    assert_eq!(RES_LAB.load(Ordering::Relaxed), 0);
+    assert_eq!(RES_PORT.load(Ordering::Relaxed), 0);
+
+    hold(&RES_HUB);
+}
+
+#[etest(consumes="lab/hub1/port1")]
+fn test_2() {
+    assert_eq!(RES_LAB.load(Ordering::Relaxed), 0);
+    assert_eq!(RES_HUB.load(Ordering::Relaxed), 0);
+
+    hold(&RES_PORT);
+}
+
+#[etest(uses="lab/hub1/port2")]
+fn test_3() {
+    assert_eq!(RES_LAB.load(Ordering::Relaxed), 0);
+    assert_eq!(RES_HUB.load(Ordering::Relaxed), 0);
+
+    hold(&RES_PORT);
+}
+
+#[etest(consumes="lab/hub2/port1", test_fn=())]
+fn test_inner_4() {
+}
+
+// children of a consumed resource can be reserved by nested tests
+#[etest(consumes="lab/hub2", timeout=2_000)]
+fn test_outer_4() {
+    test_inner_4();
+}
+
+// a test which uses a child can not consume its parent; this is detected as
+// a deadlock
+#[should_panic]
+#[etest(uses="lab/hub3/port1")]
+fn test_outer_5() {
+    test_inner_5();
+}
+
+#[etest(consumes="lab/hub3", test_fn=())]
+fn test_inner_5() {
+}
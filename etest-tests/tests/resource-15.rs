@@ -0,0 +1,75 @@
+//! Tests resource groups
+
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+use std::thread::sleep;
+
+use etest::prelude::*;
+
+static RES_VIDEO:	AtomicU64 = AtomicU64::new(0);
+static RES_HDMI:	AtomicU64 = AtomicU64::new(0);
+static RES_NETWORK:	AtomicU64 = AtomicU64::new(0);
+
+fn output() -> ResourceId {
+    "group-hdmi".into()
+}
+
+resource_group!(MEDIA = ["group-video", "group-audio", output()]);
+resource_group!(LAB = [MEDIA, Slots("group-network", 2)]);
+resource_group!(EMPTY = []);
+
+fn hold(res: &AtomicU64) {
+    res.fetch_add(1, Ordering::Relaxed);
+    sleep(Duration::from_millis(300));
+    res.fetch_sub(1, Ordering::Relaxed);
+}
+
+fn hold_media() {
+    assert_eq!(RES_HDMI.fetch_add(1, Ordering::Relaxed), 0);
+    hold(&RES_VIDEO);
+    RES_HDMI.fetch_sub(1, Ordering::Relaxed);
+}
+
+#[etest(consumes=[MEDIA])]
+fn test_0() {
+    assert_eq!(RES_VIDEO.load(Ordering::Relaxed), 0);
+    hold_media();
+}
+
+#[etest(consumes="group-video")]
+fn test_1() {
+    assert_eq!(RES_VIDEO.load(Ordering::Relaxed), 0);
+    hold(&RES_VIDEO);
+}
+
+#[etest(consumes="group-hdmi")]
+fn test_2() {
+    assert_eq!(RES_HDMI.load(Ordering::Relaxed), 0);
+    hold(&RES_HDMI);
+}
+
+#[etest(consumes=[LAB])]
+fn test_3() {
+    assert_eq!(RES_VIDEO.load(Ordering::Relaxed), 0);
+    assert!(RES_NETWORK.load(Ordering::Relaxed) < 2);
+
+    hold_media();
+}
+
+#[etest(consumes=[Slots("group-network", 2)])]
+fn test_4() {
+    assert!(RES_NETWORK.load(Ordering::Relaxed) < 2);
+    hold(&RES_NETWORK);
+}
+
+#[etest(consumes=[Slots("group-network", 2)])]
+fn test_5() {
+    assert!(RES_NETWORK.load(Ordering::Relaxed) < 2);
+    hold(&RES_NETWORK);
+}
+
+#[etest(uses=[EMPTY], consumes=[MEDIA, "group-video"])]
+fn test_6() {
+    assert_eq!(RES_VIDEO.load(Ordering::Relaxed), 0);
+    hold_media();
+}
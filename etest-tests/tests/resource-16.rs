@@ -0,0 +1,89 @@
+//! Tests snapshots of the resource state
+
+use std::time::Duration;
+use std::thread::sleep;
+
+use etest::prelude::*;
+use etest::resources::snapshot;
+
+#[etest(consumes="snap-A", no_default_uses, test_fn=())]
+fn test_inner_0() {
+}
+
+#[etest(consumes="snap-A", uses=[Slots("snap-B", 4)], no_default_uses)]
+fn test_outer_0() {
+    std::thread::spawn(test_inner_0);
+
+    sleep(Duration::from_millis(300));
+
+    let snapshot = snapshot();
+    let res_a = snapshot.resources.iter()
+        .find(|r| r.id == "snap-A".into())
+        .unwrap();
+    let res_b = snapshot.resources.iter()
+        .find(|r| r.id == "snap-B".into())
+        .unwrap();
+
+    assert_eq!(res_a.owners.len(), 1);
+    assert!(res_a.owners[0].test.to_string().contains("test_outer_0"));
+    assert!(res_a.users.is_empty());
+
+    assert_eq!(res_b.capacity, 4);
+    assert!(res_b.owners.is_empty());
+    assert_eq!(res_b.users.len(), 1);
+
+    let waiter = snapshot.waiters.iter()
+        .find(|w| w.consumes.contains(&"snap-A".into()))
+        .unwrap();
+
+    assert!(waiter.uses.is_empty());
+
+    let table = snapshot.to_string();
+
+    assert!(table.starts_with("RESOURCE"));
+    assert!(table.contains("snap-A"));
+    assert!(table.contains("\nWAITING"));
+}
+
+#[etest(uses="snap-C", priority=7, test_fn=())]
+fn test_inner_1() {
+}
+
+#[etest(consumes="snap-C")]
+fn test_outer_1() {
+    std::thread::spawn(test_inner_1);
+
+    sleep(Duration::from_millis(300));
+
+    let snapshot = snapshot();
+    let waiter = snapshot.waiters.iter()
+        .find(|w| w.uses.contains(&"snap-C".into()))
+        .unwrap();
+
+    assert_eq!(waiter.priority, 7);
+    assert!(waiter.uses.contains(&ResourceId::Basic));
+}
+
+#[etest(consumes_any=["snap-E", "snap-D"], test_fn=())]
+fn test_inner_2() {
+}
+
+#[etest(consumes=["snap-D", "snap-E"])]
+fn test_outer_2() {
+    std::thread::spawn(test_inner_2);
+
+    sleep(Duration::from_millis(300));
+
+    let snapshot = snapshot();
+    let waiter = snapshot.waiters.iter()
+        .find(|w| w.consumes_any.contains(&"snap-D".into()))
+        .unwrap();
+
+    assert_eq!(waiter.consumes_any, ["snap-E".into(), "snap-D".into()]);
+    assert!(waiter.consumes.is_empty());
+
+    let table = snapshot.to_string();
+
+    assert!(table.contains("CONSUMES ANY"));
+    assert!(table.contains("snap-E, snap-D"));
+}
@@ -0,0 +1,125 @@
+//! Tests setup and teardown callbacks of resources
+
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::time::Duration;
+use std::thread::sleep;
+
+use etest::prelude::*;
+
+static A_ACTIVE:	AtomicBool = AtomicBool::new(false);
+static A_SETUPS:	AtomicU64 = AtomicU64::new(0);
+
+static B_ACTIVE:	AtomicBool = AtomicBool::new(false);
+static B_SETUPS:	AtomicU64 = AtomicU64::new(0);
+static B_TEARDOWNS:	AtomicU64 = AtomicU64::new(0);
+
+static C_SETUPS:	AtomicU64 = AtomicU64::new(0);
+
+fn setup_a() {
+    // tests must wait for the setup
+    sleep(Duration::from_millis(200));
+
+    assert!(!A_ACTIVE.swap(true, Ordering::SeqCst));
+    A_SETUPS.fetch_add(1, Ordering::SeqCst);
+}
+
+fn teardown_a() {
+    assert!(A_ACTIVE.swap(false, Ordering::SeqCst));
+}
+
+const RES_A: Lifecycle<&str> = Lifecycle {
+    resource:	"lifecycle-A",
+    setup:	setup_a,
+    teardown:	teardown_a,
+};
+
+const RES_B: Lifecycle<&str> = Lifecycle {
+    resource:	"lifecycle-B",
+    setup:	|| {
+        assert!(!B_ACTIVE.swap(true, Ordering::SeqCst));
+        B_SETUPS.fetch_add(1, Ordering::SeqCst);
+    },
+    teardown:	|| {
+        assert!(B_ACTIVE.swap(false, Ordering::SeqCst));
+        B_TEARDOWNS.fetch_add(1, Ordering::SeqCst);
+    },
+};
+
+const RES_C: Lifecycle<&str> = Lifecycle {
+    resource:	"lifecycle-C",
+    setup:	|| {
+        // fails on the first try
+        if C_SETUPS.fetch_add(1, Ordering::SeqCst) == 0 {
+            panic!("setup failed");
+        }
+    },
+    teardown:	|| {},
+};
+
+#[etest(uses=[RES_A])]
+fn test_0() {
+    assert!(A_ACTIVE.load(Ordering::SeqCst));
+    sleep(Duration::from_millis(100));
+}
+
+#[etest(uses=[RES_A])]
+fn test_1() {
+    assert!(A_ACTIVE.load(Ordering::SeqCst));
+    sleep(Duration::from_millis(100));
+}
+
+#[etest(consumes=[RES_A])]
+fn test_2() {
+    assert!(A_ACTIVE.load(Ordering::SeqCst));
+    sleep(Duration::from_millis(100));
+}
+
+#[etest(uses=[RES_A], test_fn=())]
+fn test_inner_3() {
+    assert!(A_ACTIVE.load(Ordering::SeqCst));
+}
+
+// nested reservations do not set up the resource again
+#[etest(consumes=[RES_A])]
+fn test_outer_3() {
+    let setups = A_SETUPS.load(Ordering::SeqCst);
+
+    test_inner_3();
+
+    assert!(A_ACTIVE.load(Ordering::SeqCst));
+    assert_eq!(A_SETUPS.load(Ordering::SeqCst), setups);
+}
+
+#[etest(uses=[RES_B], test_fn=())]
+fn test_inner_4() {
+    assert!(B_ACTIVE.load(Ordering::SeqCst));
+}
+
+// resource is torn down when the last test releases it
+#[etest]
+fn test_outer_4() {
+    test_inner_4();
+
+    assert!(!B_ACTIVE.load(Ordering::SeqCst));
+    assert_eq!(B_TEARDOWNS.load(Ordering::SeqCst), 1);
+
+    test_inner_4();
+
+    assert!(!B_ACTIVE.load(Ordering::SeqCst));
+    assert_eq!(B_SETUPS.load(Ordering::SeqCst), 2);
+    assert_eq!(B_TEARDOWNS.load(Ordering::SeqCst), 2);
+}
+
+#[etest(consumes=[RES_C], test_fn=())]
+fn test_inner_5() {
+}
+
+// failed setup is retried by the next reservation
+#[etest]
+fn test_outer_5() {
+    assert!(std::panic::catch_unwind(test_inner_5).is_err());
+
+    test_inner_5();
+
+    assert_eq!(C_SETUPS.load(Ordering::SeqCst), 2);
+}
@@ -0,0 +1,169 @@
+//! Tests resources which guard a value
+
+use std::future::Future;
+use std::pin::pin;
+use std::sync::Arc;
+use std::task::{ Context, Poll, Wake, Waker };
+use std::time::Duration;
+use std::thread::{ sleep, Thread };
+
+use etest::prelude::*;
+
+static COUNTER:	Resource<u64> = Resource::new("typed-counter", 0);
+static LOG:	Resource<Vec<&str>> = Resource::new("typed-log", Vec::new());
+
+fn increment() {
+    let val = *COUNTER.write();
+
+    sleep(Duration::from_millis(100));
+
+    *COUNTER.write() = val + 1;
+}
+
+#[etest(consumes=[&COUNTER])]
+fn test_0() {
+    increment();
+}
+
+#[etest(consumes=[&COUNTER])]
+fn test_1() {
+    increment();
+}
+
+#[etest(consumes=[&COUNTER], timeout=2_000)]
+fn test_2() {
+    increment();
+}
+
+#[etest(uses=[&COUNTER])]
+fn test_3() {
+    let val = *COUNTER.read();
+
+    sleep(Duration::from_millis(100));
+
+    assert_eq!(*COUNTER.read(), val);
+}
+
+#[should_panic]
+#[etest(uses=[&COUNTER])]
+fn test_4() {
+    *COUNTER.write() += 1;
+}
+
+#[should_panic]
+#[etest]
+fn test_5() {
+    println!("{}", *COUNTER.read());
+}
+
+#[etest(uses=[&LOG], test_fn=())]
+fn test_inner_6() {
+    assert_eq!(LOG.read().as_slice(), ["outer"]);
+}
+
+#[etest(consumes=[&LOG])]
+fn test_outer_6() {
+    LOG.write().push("outer");
+
+    test_inner_6();
+
+    LOG.write().clear();
+}
+
+#[should_panic]
+#[etest(consumes=[&LOG])]
+fn test_7() {
+    // spawned threads do not run in the context of the test
+    std::thread::spawn(|| LOG.write().push("thread"))
+        .join()
+        .unwrap_or_else(|e| std::panic::resume_unwind(e));
+}
+
+static STATE:	Resource<u32> = Resource::new("typed-state", 0);
+
+#[etest(no_default_uses)]
+fn test_8() {
+    let guard = etest::reserve().consumes(&STATE).lock();
+
+    *guard.write(&STATE) += 1;
+
+    // the guard gives access to threads which are spawned by the test
+    std::thread::scope(|s| {
+        s.spawn(|| assert_eq!(*guard.read(&STATE), 1));
+    });
+
+    *guard.write(&STATE) -= 1;
+}
+
+#[should_panic]
+#[etest(no_default_uses)]
+fn test_9() {
+    let guard = etest::reserve().uses(&STATE).lock();
+
+    *guard.write(&STATE) += 1;
+}
+
+#[should_panic]
+#[etest(no_default_uses)]
+fn test_10() {
+    let guard = etest::reserve().uses("typed-other").lock();
+
+    println!("{}", *guard.read(&STATE));
+}
+
+struct Unpark(Thread);
+
+impl Wake for Unpark {
+    fn wake(self: Arc<Self>) {
+        self.0.unpark();
+    }
+}
+
+/// Minimal executor for running a future on the current thread
+fn block_on<F: Future>(f: F) -> F::Output {
+    let mut f = pin!(f);
+    let waker = Waker::from(Arc::new(Unpark(std::thread::current())));
+    let mut cx = Context::from_waker(&waker);
+
+    loop {
+        match f.as_mut().poll(&mut cx) {
+            Poll::Ready(v)	=> break v,
+            Poll::Pending	=> std::thread::park(),
+        }
+    }
+}
+
+/// Returns `Pending` once; the test continues in a later poll
+async fn yield_now() {
+    let mut yielded = false;
+
+    std::future::poll_fn(|cx| match std::mem::replace(&mut yielded, true) {
+        true	=> Poll::Ready(()),
+        false	=> {
+            cx.waker().wake_by_ref();
+            Poll::Pending
+        },
+    }).await
+}
+
+static ASYNC:	Resource<u32> = Resource::new("typed-async", 0);
+
+#[etest(consumes=[&ASYNC], test_fn=())]
+async fn test_inner_11() {
+    *ASYNC.write() += 1;
+    yield_now().await;
+    *ASYNC.write() -= 1;
+}
+
+#[etest(consumes=[&ASYNC], timeout=2_000, test_fn=())]
+async fn test_inner_12() {
+    yield_now().await;
+    assert_eq!(*ASYNC.read(), 0);
+}
+
+// async tests access the resources in every poll
+#[test]
+fn test_11() {
+    block_on(test_inner_11());
+    block_on(test_inner_12());
+}
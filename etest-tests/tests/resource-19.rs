@@ -0,0 +1,174 @@
+//! Tests consumption of any resource of a pool
+
+use std::future::Future;
+use std::pin::pin;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::task::{ Context, Poll, Wake, Waker };
+use std::time::Duration;
+use std::thread::{ sleep, Thread };
+
+use etest::prelude::*;
+use etest::resources::selected;
+
+static DEVICES:	[AtomicBool; 3] = [
+    AtomicBool::new(false),
+    AtomicBool::new(false),
+    AtomicBool::new(false),
+];
+
+const POOL: [&str; 3] = ["pool-dev0", "pool-dev1", "pool-dev2"];
+
+fn selected_device() -> usize {
+    let dev = selected().unwrap();
+
+    POOL.iter().position(|d| dev == (*d).into()).unwrap()
+}
+
+fn use_device(idx: usize) {
+    assert!(!DEVICES[idx].swap(true, Ordering::SeqCst));
+    sleep(Duration::from_millis(300));
+    DEVICES[idx].store(false, Ordering::SeqCst);
+}
+
+#[etest(consumes_any=["pool-dev0", "pool-dev1", "pool-dev2"])]
+fn test_0() {
+    use_device(selected_device());
+}
+
+#[etest(consumes_any=["pool-dev0", "pool-dev1", "pool-dev2"])]
+fn test_1() {
+    use_device(selected_device());
+}
+
+#[etest(consumes_any=[POOL[0], POOL[1], POOL[2]], timeout=5_000)]
+fn test_2() {
+    use_device(selected_device());
+}
+
+#[etest(consumes_any=[POOL[2], POOL[1], POOL[0]])]
+fn test_3() {
+    use_device(selected_device());
+}
+
+#[etest(consumes="pool-dev1")]
+fn test_4() {
+    assert_eq!(selected(), None);
+    use_device(1);
+}
+
+#[etest(consumes_any=["pool-dev0", "pool-dev1", "pool-dev2"], test_fn=())]
+fn test_inner_5(dev: usize) {
+    assert_eq!(selected_device(), dev);
+}
+
+// nested reservations prefer the resource held by the test
+#[etest(consumes_any=["pool-dev0", "pool-dev1", "pool-dev2"])]
+fn test_outer_5() {
+    let dev = selected_device();
+
+    test_inner_5(dev);
+
+    assert_eq!(selected_device(), dev);
+    use_device(dev);
+}
+
+// candidates which are consumed by the test itself are granted
+#[etest(consumes=["pool-X", "pool-Y"])]
+fn test_outer_6() {
+    test_inner_6("pool-X");
+}
+
+#[etest(consumes_any=["pool-W", "pool-X", "pool-Y"], test_fn=())]
+fn test_inner_6(res: &str) {
+    assert_eq!(selected(), Some(res.into()));
+}
+
+// all candidates are used by the test itself
+#[should_panic]
+#[etest(uses=["pool-U", "pool-V"])]
+fn test_outer_7() {
+    test_inner_7();
+}
+
+#[etest(consumes_any=["pool-U", "pool-V"], test_fn=())]
+fn test_inner_7() {
+}
+
+// only the parents of the selected candidate are used
+#[test]
+fn test_8() {
+    let (tx_held, rx_held) = std::sync::mpsc::channel();
+    let (tx_done, rx_done) = std::sync::mpsc::channel::<()>();
+
+    let holder = std::thread::spawn(move || {
+        let _guard = etest::reserve().consumes("usb/hub2").lock();
+
+        tx_held.send(()).unwrap();
+        let _ = rx_done.recv();
+    });
+
+    rx_held.recv().unwrap();
+
+    {
+        let _guard = etest::reserve()
+            .consumes_any("usb/hub1/p1")
+            .consumes_any("usb/hub2/p1")
+            .lock_timeout(Duration::from_secs(2))
+            .lock();
+
+        assert_eq!(selected(), Some("usb/hub1/p1".into()));
+    }
+
+    drop(tx_done);
+    holder.join().unwrap();
+}
+
+struct Unpark(Thread);
+
+impl Wake for Unpark {
+    fn wake(self: Arc<Self>) {
+        self.0.unpark();
+    }
+}
+
+/// Minimal executor for running a future on the current thread
+fn block_on<F: Future>(f: F) -> F::Output {
+    let mut f = pin!(f);
+    let waker = Waker::from(Arc::new(Unpark(std::thread::current())));
+    let mut cx = Context::from_waker(&waker);
+
+    loop {
+        match f.as_mut().poll(&mut cx) {
+            Poll::Ready(v)	=> break v,
+            Poll::Pending	=> std::thread::park(),
+        }
+    }
+}
+
+/// Returns `Pending` once; the test continues in a later poll
+async fn yield_now() {
+    let mut yielded = false;
+
+    std::future::poll_fn(|cx| match std::mem::replace(&mut yielded, true) {
+        true	=> Poll::Ready(()),
+        false	=> {
+            cx.waker().wake_by_ref();
+            Poll::Pending
+        },
+    }).await
+}
+
+#[etest(consumes_any=["pool-async0", "pool-async1"], test_fn=())]
+async fn test_inner_9() {
+    let dev = selected().unwrap();
+
+    yield_now().await;
+    assert_eq!(selected(), Some(dev));
+}
+
+// async tests see the selection in every poll
+#[test]
+fn test_9() {
+    block_on(test_inner_9());
+}
@@ -0,0 +1,80 @@
+//! Tests reservations within the test body
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+use std::thread::sleep;
+
+use etest::prelude::*;
+
+static FLASH:	AtomicBool = AtomicBool::new(false);
+
+fn program_flash() {
+    let _flash = etest::reserve().consumes("raii-flash").lock();
+
+    assert!(!FLASH.swap(true, Ordering::SeqCst));
+    sleep(Duration::from_millis(100));
+    FLASH.store(false, Ordering::SeqCst);
+}
+
+#[etest]
+fn test_0() {
+    program_flash();
+    program_flash();
+}
+
+#[etest(timeout=2_000)]
+fn test_1() {
+    program_flash();
+}
+
+#[test]
+fn test_2() {
+    program_flash();
+}
+
+// resource is released when the guard is dropped
+#[etest]
+fn test_3() {
+    let guard = etest::reserve().consumes("raii-A").lock();
+
+    let other = std::thread::spawn(|| {
+        etest::reserve()
+            .consumes("raii-A")
+            .lock_timeout(2_000)
+            .lock()
+    });
+
+    sleep(Duration::from_millis(200));
+    assert!(!other.is_finished());
+
+    drop(guard);
+
+    other.join().unwrap();
+}
+
+#[etest]
+fn test_4() {
+    let guard = etest::reserve()
+        .consumes_any("raii-dev0")
+        .consumes_any("raii-dev1")
+        .lock();
+
+    assert!(guard.selected().is_some());
+    assert_eq!(guard.selected(), etest::resources::selected());
+
+    drop(guard);
+
+    assert_eq!(etest::resources::selected(), None);
+}
+
+// test holds the resource already
+#[etest(consumes="raii-B")]
+fn test_5() {
+    let _guard = etest::reserve().uses("raii-B").lock();
+}
+
+#[should_panic]
+#[etest(uses="raii-C")]
+fn test_6() {
+    let _guard = etest::reserve().consumes("raii-C").lock();
+}
@@ -0,0 +1,215 @@
+//! Tests upgrading used resources to exclusive use
+
+use std::future::Future;
+use std::pin::pin;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::mpsc::channel;
+use std::task::{ Context, Poll, Wake, Waker };
+use std::time::{Duration, Instant};
+use std::thread::{ sleep, Thread };
+
+use etest::prelude::*;
+use etest::resources::snapshot;
+
+static READERS:	AtomicUsize = AtomicUsize::new(0);
+static FLASHING:	AtomicBool = AtomicBool::new(false);
+
+static STATE:	Resource<u32> = Resource::new("upgrade-state", 0);
+
+fn read_state() {
+    assert!(!FLASHING.load(Ordering::SeqCst));
+    READERS.fetch_add(1, Ordering::SeqCst);
+    sleep(Duration::from_millis(200));
+    assert!(!FLASHING.load(Ordering::SeqCst));
+    READERS.fetch_sub(1, Ordering::SeqCst);
+}
+
+fn flash() {
+    assert_eq!(READERS.load(Ordering::SeqCst), 0);
+    assert!(!FLASHING.swap(true, Ordering::SeqCst));
+    sleep(Duration::from_millis(200));
+    FLASHING.store(false, Ordering::SeqCst);
+}
+
+fn owners(id: &str) -> usize {
+    snapshot().resources.iter()
+        .find(|r| r.id == id.into())
+        .map_or(0, |r| r.owners.len())
+}
+
+#[etest(uses="upgrade-dev")]
+fn test_0() {
+    read_state();
+}
+
+#[etest(uses="upgrade-dev")]
+fn test_1() {
+    read_state();
+    read_state();
+}
+
+#[etest(uses="upgrade-dev")]
+fn test_2() {
+    let mut dev = etest::reserve().uses("upgrade-dev").lock();
+
+    read_state();
+
+    dev.upgrade();
+    flash();
+    dev.downgrade();
+
+    read_state();
+}
+
+// reservations which arrive while upgrading are served after the downgrade
+#[test]
+fn test_3() {
+    static UPGRADED: AtomicBool = AtomicBool::new(false);
+
+    let mut guard = etest::reserve().uses("upgrade-A").lock();
+    let (tx, rx) = channel();
+
+    let reader = std::thread::spawn(move || {
+        let _guard = etest::reserve().uses("upgrade-A").lock();
+
+        tx.send(()).unwrap();
+        sleep(Duration::from_millis(300));
+    });
+
+    rx.recv().unwrap();
+
+    let late = std::thread::spawn(|| {
+        sleep(Duration::from_millis(100));
+
+        let _guard = etest::reserve().uses("upgrade-A").lock();
+
+        assert!(!UPGRADED.load(Ordering::SeqCst));
+    });
+
+    let start = Instant::now();
+
+    guard.upgrade();
+    assert!(start.elapsed() >= Duration::from_millis(250));
+
+    UPGRADED.store(true, Ordering::SeqCst);
+    sleep(Duration::from_millis(200));
+    assert!(!late.is_finished());
+    UPGRADED.store(false, Ordering::SeqCst);
+
+    guard.downgrade();
+
+    late.join().unwrap();
+    reader.join().unwrap();
+}
+
+// upgraded typed resources can be written
+#[etest(uses=[&STATE])]
+fn test_4() {
+    let mut guard = etest::reserve().uses(&STATE).lock();
+
+    guard.upgrade();
+    *STATE.write() += 1;
+    guard.downgrade();
+
+    assert_eq!(*STATE.read(), 1);
+}
+
+// dropping the guard downgrades a reservation which is shared with the test
+#[etest(uses="upgrade-B")]
+fn test_5() {
+    {
+        let mut guard = etest::reserve().uses("upgrade-B").lock();
+
+        guard.upgrade();
+        assert_eq!(owners("upgrade-B"), 1);
+    }
+
+    assert_eq!(owners("upgrade-B"), 0);
+}
+
+// consumed resources are not changed
+#[etest(consumes="upgrade-C")]
+fn test_6() {
+    let mut guard = etest::reserve().consumes("upgrade-C").lock();
+
+    guard.upgrade();
+    guard.downgrade();
+
+    assert_eq!(owners("upgrade-C"), 1);
+}
+
+// two users try to upgrade the resource
+#[should_panic]
+#[test]
+fn test_7() {
+    let mut guard = etest::reserve().uses("upgrade-D").lock();
+    let (tx, rx) = channel();
+
+    let other = std::thread::spawn(move || {
+        let mut guard = etest::reserve().uses("upgrade-D").lock();
+
+        tx.send(()).unwrap();
+        sleep(Duration::from_millis(200));
+        guard.upgrade();
+    });
+
+    rx.recv().unwrap();
+
+    guard.upgrade();
+
+    other.join().unwrap_or_else(|e| std::panic::resume_unwind(e));
+}
+
+struct Unpark(Thread);
+
+impl Wake for Unpark {
+    fn wake(self: Arc<Self>) {
+        self.0.unpark();
+    }
+}
+
+/// Minimal executor for running a future on the current thread
+fn block_on<F: Future>(f: F) -> F::Output {
+    let mut f = pin!(f);
+    let waker = Waker::from(Arc::new(Unpark(std::thread::current())));
+    let mut cx = Context::from_waker(&waker);
+
+    loop {
+        match f.as_mut().poll(&mut cx) {
+            Poll::Ready(v)	=> break v,
+            Poll::Pending	=> std::thread::park(),
+        }
+    }
+}
+
+/// Returns `Pending` once; the test continues in a later poll
+async fn yield_now() {
+    let mut yielded = false;
+
+    std::future::poll_fn(|cx| match std::mem::replace(&mut yielded, true) {
+        true	=> Poll::Ready(()),
+        false	=> {
+            cx.waker().wake_by_ref();
+            Poll::Pending
+        },
+    }).await
+}
+
+#[etest(uses="upgrade-E", test_fn=())]
+async fn test_inner_8() {
+    yield_now().await;
+
+    // the nested reservation shares the one of the test
+    let mut guard = etest::reserve().uses("upgrade-E").lock_async().await;
+
+    guard.upgrade();
+    assert_eq!(owners("upgrade-E"), 1);
+    guard.downgrade();
+}
+
+// async tests can upgrade their resources
+#[test]
+fn test_8() {
+    block_on(test_inner_8());
+}
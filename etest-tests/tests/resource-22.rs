@@ -0,0 +1,71 @@
+//! Tests the statistics of resource contention
+#![cfg(feature = "stats")]
+
+use std::process::Command;
+use std::time::Duration;
+use std::thread::sleep;
+
+use etest::prelude::*;
+
+#[ignore]
+#[etest(consumes="stats-A", uses="stats-B")]
+fn child_0() {
+    sleep(Duration::from_millis(300));
+}
+
+#[ignore]
+#[etest(consumes="stats-A", uses="stats-B")]
+fn child_1() {
+    sleep(Duration::from_millis(300));
+}
+
+/// Returns the columns of the report line which ends with `suffix`
+fn columns<'a>(report: &'a str, suffix: &str) -> Vec<&'a str> {
+    report.lines()
+        .find(|l| l.ends_with(suffix))
+        .unwrap()
+        .split_whitespace()
+        .collect()
+}
+
+fn seconds(s: &str) -> f64 {
+    s.strip_suffix('s').unwrap().parse().unwrap()
+}
+
+#[test]
+fn test_0() {
+    let file = std::env::temp_dir().join(format!("etest-stats-{}.txt", std::process::id()));
+
+    let _ = std::fs::remove_file(&file);
+
+    // runs the children in parallel in a new process; the report is
+    // written when it exits
+    let status = Command::new(std::env::current_exe().unwrap())
+        .args(["child_", "--ignored", "--test-threads=2"])
+        .env("ETEST_STATS_FILE", &file)
+        .status()
+        .unwrap();
+
+    assert!(status.success());
+
+    let report = std::fs::read_to_string(&file).unwrap();
+
+    std::fs::remove_file(&file).unwrap();
+
+    // reservations, contended, waited, held
+    let res_a = columns(&report, " stats-A");
+    let res_b = columns(&report, " stats-B");
+
+    assert_eq!(res_a[0..2], ["2", "1"]);
+    assert!(seconds(res_a[2]) >= 0.25);
+    assert!(seconds(res_a[3]) >= 0.55);
+
+    // "stats-B" is used by both tests; it does not block them
+    assert_eq!(res_b[0..2], ["2", "0"]);
+    assert_eq!(seconds(res_b[2]), 0.0);
+
+    let child_0 = columns(&report, "(child_0)");
+    let child_1 = columns(&report, "(child_1)");
+
+    assert!(seconds(child_0[0]) + seconds(child_1[0]) >= 0.25);
+}
@@ -0,0 +1,105 @@
+//! Benchmarks releasing resources while many tests wait for other ones
+//!
+//! Waiting tests are woken only by changes of the resources which they
+//! need; releasing other resources must not wake them.  Run with
+//! `--nocapture` to see the timings.
+//!
+//! With `flock`, waiters poll the lock files periodically and are woken
+//! without changes; these retries must not slow down the other tests.
+
+use std::future::Future;
+use std::pin::pin;
+use std::sync::Arc;
+use std::sync::atomic::{ AtomicBool, Ordering };
+use std::sync::mpsc::channel;
+use std::task::{ Context, Wake, Waker };
+use std::time::{ Duration, Instant };
+
+const WAITERS: usize = 200;
+const CYCLES: usize = 2_000;
+
+struct Flag(AtomicBool);
+
+impl Wake for Flag {
+    fn wake(self: Arc<Self>) {
+        self.0.store(true, Ordering::SeqCst);
+    }
+}
+
+/// Returns the time for reserving and releasing an unrelated resource
+/// `CYCLES` times
+fn cycles() -> Duration {
+    let start = Instant::now();
+
+    for _ in 0..CYCLES {
+        let _guard = etest::reserve()
+            .consumes("bench-X")
+            .uses("bench-shared")
+            .lock();
+    }
+
+    start.elapsed()
+}
+
+#[test]
+fn test_0() {
+    let idle = cycles();
+
+    let (tx, rx) = channel();
+    let (release_tx, release_rx) = channel::<()>();
+
+    let holder = std::thread::spawn(move || {
+        let guard = (0..=WAITERS)
+            .fold(etest::reserve(), |b, i| b.consumes(format!("bench-{i}")))
+            .lock();
+
+        tx.send(()).unwrap();
+        let _ = release_rx.recv();
+
+        drop(guard);
+    });
+
+    rx.recv().unwrap();
+
+    let waiters: Vec<_> = (0..WAITERS)
+        .map(|i| std::thread::spawn(move || {
+            let _guard = etest::reserve()
+                .consumes(format!("bench-{i}"))
+                .uses("bench-shared")
+                .lock();
+        }))
+        .collect();
+
+    // an async waiter tells whether it has been woken
+    let flag = Arc::new(Flag(AtomicBool::new(false)));
+    let waker = Waker::from(flag.clone());
+    let mut cx = Context::from_waker(&waker);
+    let mut fut = pin!(etest::reserve()
+                       .consumes(format!("bench-{WAITERS}"))
+                       .uses("bench-shared")
+                       .lock_async());
+
+    assert!(fut.as_mut().poll(&mut cx).is_pending());
+
+    // wait until all are queued
+    while etest::resources::snapshot().waiters.len() <= WAITERS {
+        std::thread::sleep(Duration::from_millis(10));
+    }
+
+    let busy = cycles();
+
+    println!("{CYCLES} cycles: {idle:?} without waiters, {busy:?} with {} waiters", WAITERS + 1);
+
+    // with `flock`, waiters are woken to poll the lock files
+    assert!(cfg!(feature = "flock") || !flag.0.load(Ordering::SeqCst));
+
+    drop(release_tx);
+    holder.join().unwrap();
+
+    for w in waiters {
+        w.join().unwrap();
+    }
+
+    assert!(flag.0.load(Ordering::SeqCst));
+    assert!(fut.as_mut().poll(&mut cx).is_ready());
+}
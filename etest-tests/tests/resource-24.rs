@@ -0,0 +1,120 @@
+//! Tests ordering of tests by 'after' and 'before'
+
+use std::process::{ Command, Output };
+use std::sync::atomic::{ AtomicBool, Ordering };
+use std::time::Duration;
+use std::thread::sleep;
+
+use etest::prelude::*;
+
+static PROVISIONED: AtomicBool = AtomicBool::new(false);
+static SETUP: AtomicBool = AtomicBool::new(false);
+
+#[etest(no_default_uses)]
+fn test_0_provision() {
+    sleep(Duration::from_millis(300));
+    PROVISIONED.store(true, Ordering::SeqCst);
+}
+
+#[etest(no_default_uses, after=[test_0_provision])]
+fn test_1_verify() {
+    assert!(PROVISIONED.load(Ordering::SeqCst));
+}
+
+#[etest(no_default_uses, before=[self::test_3_check])]
+fn test_2_setup() {
+    sleep(Duration::from_millis(300));
+    SETUP.store(true, Ordering::SeqCst);
+}
+
+#[etest(no_default_uses)]
+fn test_3_check() {
+    assert!(SETUP.load(Ordering::SeqCst));
+}
+
+// sorts after the dependencies
+mod verify {
+    use super::*;
+
+    #[etest(no_default_uses, after=[super::test_0_provision, crate::test_2_setup])]
+    fn test_4_verify() {
+        assert!(PROVISIONED.load(Ordering::SeqCst));
+        assert!(SETUP.load(Ordering::SeqCst));
+    }
+}
+
+/// Tells whether the test runs outside of [`run_child()`]; the child tests
+/// are skipped then
+fn is_parent() -> bool {
+    std::env::var_os("ETEST_CHILD").is_none()
+}
+
+// the dependency sorts after the dependent test; it is started later by the
+// harness
+#[etest(no_default_uses, skip=is_parent(), after=[child_b_provision])]
+fn child_a_verify() {
+    println!("VERIFY provisioned={}", PROVISIONED.load(Ordering::SeqCst));
+}
+
+#[etest(no_default_uses, skip=is_parent())]
+fn child_b_provision() {
+    sleep(Duration::from_millis(300));
+    PROVISIONED.store(true, Ordering::SeqCst);
+}
+
+#[etest(no_default_uses, skip=is_parent(), after=[cycle_d])]
+fn cycle_c() {}
+
+#[etest(no_default_uses, skip=is_parent(), after=[cycle_c])]
+fn cycle_d() {}
+
+fn run_child(args: &[&str]) -> Output {
+    Command::new(std::env::current_exe().unwrap())
+        .env("ETEST_CHILD", "1")
+        .arg("--nocapture")
+        .args(args)
+        .output()
+        .unwrap()
+}
+
+fn stdout(output: &Output) -> String {
+    String::from_utf8_lossy(&output.stdout).into_owned()
+}
+
+fn stderr(output: &Output) -> String {
+    String::from_utf8_lossy(&output.stderr).into_owned()
+}
+
+#[test]
+fn test_5() {
+    // the dependency runs in parallel and is waited for
+    let output = run_child(&["child_", "--test-threads=2"]);
+
+    assert!(output.status.success());
+    assert!(stdout(&output).contains("VERIFY provisioned=true"));
+
+    // the dependency is filtered out; the test does not wait
+    let output = run_child(&["child_a_verify", "--test-threads=2"]);
+
+    assert!(output.status.success());
+    assert!(stdout(&output).contains("VERIFY provisioned=false"));
+
+    let output = run_child(&["child_", "--skip", "child_b", "--test-threads=2"]);
+
+    assert!(output.status.success());
+    assert!(stdout(&output).contains("VERIFY provisioned=false"));
+}
+
+#[test]
+fn test_6() {
+    // a single thread can not run the dependency while the test waits
+    let output = run_child(&["child_", "--test-threads=1"]);
+
+    assert!(!output.status.success());
+    assert!(stderr(&output).contains("ORDER DEADLOCK"));
+
+    let output = run_child(&["cycle_", "--test-threads=2"]);
+
+    assert!(!output.status.success());
+    assert!(stderr(&output).contains("ordering cycle"));
+}
@@ -0,0 +1,78 @@
+//! Tests '#[derive(ResourceId)]'
+
+use etest::prelude::*;
+
+#[derive(ResourceId)]
+enum Output {
+    Hdmi,
+    Lvds,
+    #[resource(name = "display-port")]
+    DisplayPort,
+}
+
+#[derive(ResourceId)]
+pub enum Usb {
+    Hub(u8),
+    #[resource(name = "usb/hub{hub}/port{port:02}")]
+    Port { hub: u8, port: u8 },
+    Slot(u8, &'static str),
+}
+
+// runs of uppercase letters are one word
+#[allow(clippy::upper_case_acronyms)]
+#[derive(ResourceId)]
+enum Video {
+    VGA,
+    UsbHDMI,
+    HDMIPort,
+    Usb2Hdmi,
+}
+
+#[derive(ResourceId)]
+struct Lab;
+
+#[derive(ResourceId)]
+struct SerialPort(u8);
+
+#[derive(ResourceId)]
+#[resource(name = "board/{id}")]
+struct Board {
+    id:		&'static str,
+    #[allow(dead_code)]
+    rev:	Option<u8>,
+}
+
+fn id(id: impl Into<ResourceId>) -> ResourceId {
+    id.into()
+}
+
+#[test]
+fn test_0() {
+    assert_eq!(id(Output::Hdmi), ResourceId::new("hdmi"));
+    assert_eq!(id(Output::Lvds), ResourceId::new("lvds"));
+    assert_eq!(id(Output::DisplayPort), ResourceId::new("display-port"));
+
+    assert_eq!(id(Usb::Hub(1)), ResourceId::new("hub/1"));
+    assert_eq!(id(Usb::Port { hub: 1, port: 3 }), ResourceId::new("usb/hub1/port03"));
+    assert_eq!(id(Usb::Slot(2, "a")), ResourceId::new("slot/2/a"));
+
+    assert_eq!(id(Video::VGA), ResourceId::new("vga"));
+    assert_eq!(id(Video::UsbHDMI), ResourceId::new("usb_hdmi"));
+    assert_eq!(id(Video::HDMIPort), ResourceId::new("hdmi_port"));
+    assert_eq!(id(Video::Usb2Hdmi), ResourceId::new("usb2_hdmi"));
+
+    assert_eq!(id(Lab), ResourceId::new("lab"));
+    assert_eq!(id(SerialPort(3)), ResourceId::new("serial_port/3"));
+    assert_eq!(id(Board { id: "imx8", rev: None }), ResourceId::new("board/imx8"));
+}
+
+#[etest(consumes=[Output::Hdmi, Usb::Port { hub: 1, port: 3 }])]
+fn test_1() {
+    let held: Vec<_> = etest::resources::snapshot().resources.into_iter()
+        .filter(|r| !r.owners.is_empty())
+        .map(|r| r.id)
+        .collect();
+
+    assert!(held.contains(&ResourceId::new("hdmi")));
+    assert!(held.contains(&ResourceId::new("usb/hub1/port03")));
+}
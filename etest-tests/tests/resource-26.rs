@@ -0,0 +1,68 @@
+//! Tests resources which are taken from environment variables
+
+#[cfg(unix)]
+use std::path::PathBuf;
+
+use etest::prelude::*;
+
+/// Creates a file and a symlink to it
+#[cfg(unix)]
+fn device() -> (PathBuf, PathBuf) {
+    let dir = std::env::temp_dir().join(format!("etest-env-{}", std::process::id()));
+
+    let _ = std::fs::create_dir(&dir);
+
+    let dev = dir.join("ttyUSB0");
+    let link = dir.join("by-id");
+
+    std::fs::write(&dev, "").unwrap();
+
+    let _ = std::os::unix::fs::symlink(&dev, &link);
+
+    (dev, link)
+}
+
+#[test]
+fn test_0() {
+    std::env::remove_var("ETEST_ENV_0");
+    assert_eq!(etest::env_resource("ETEST_ENV_0"), ResourceId::None);
+
+    std::env::set_var("ETEST_ENV_0", "");
+    assert_eq!(etest::env_resource("ETEST_ENV_0"), ResourceId::None);
+
+    std::env::set_var("ETEST_ENV_0", "board-1");
+    assert_eq!(etest::env_resource("ETEST_ENV_0"), ResourceId::new("board-1"));
+
+    // paths which do not exist are not changed
+    std::env::set_var("ETEST_ENV_0", "/dev/etest/missing/../device");
+    assert_eq!(etest::env_resource("ETEST_ENV_0"), ResourceId::new("/dev/etest/missing/../device"));
+
+    // relative values are not resolved against the current directory
+    std::env::set_var("ETEST_ENV_0", "Cargo.toml");
+    assert_eq!(etest::env_resource("ETEST_ENV_0"), ResourceId::new("Cargo.toml"));
+
+    // paths imply the use of their parent directories
+    assert_eq!(etest::path_resource("/dev/etest-missing").parents(), [ResourceId::new("/dev")]);
+}
+
+#[cfg(unix)]
+#[test]
+fn test_1() {
+    let (dev, link) = device();
+
+    std::env::set_var("ETEST_ENV_1", &link);
+
+    assert_eq!(etest::env_resource("ETEST_ENV_1"), etest::path_resource(&dev));
+    assert_eq!(etest::path_resource(&link), etest::path_resource(&dev));
+}
+
+#[etest(skip=!etest::env_resource("ETEST_ENV_UNSET").is_some(),
+        consumes=[etest::env_resource("ETEST_ENV_UNSET")])]
+fn test_2() {
+    unreachable!();
+}
+
+// the variable is unset; the resource is ignored
+#[etest(consumes=[etest::env_resource("ETEST_ENV_UNSET")])]
+fn test_3() {
+}
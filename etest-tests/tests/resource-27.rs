@@ -0,0 +1,157 @@
+//! Tests poisoning of resources by failing tests
+
+use std::process::{ Command, Output };
+use std::sync::atomic::{ AtomicUsize, Ordering };
+
+use etest::prelude::*;
+
+const FAIL_DEV: PoisonOnFailure<&str> = PoisonOnFailure {
+    resource:	"poison-fail",
+    policy:	PoisonPolicy::Fail,
+    reset:	None,
+};
+
+const SKIP_DEV: PoisonOnFailure<&str> = PoisonOnFailure {
+    resource:	"poison-skip",
+    policy:	PoisonPolicy::Skip,
+    reset:	None,
+};
+
+static RESETS: AtomicUsize = AtomicUsize::new(0);
+
+/// fails on the first call
+fn reset() -> bool {
+    RESETS.fetch_add(1, Ordering::SeqCst) > 0
+}
+
+const RESET_DEV: PoisonOnFailure<&str> = PoisonOnFailure {
+    resource:	"poison-reset",
+    policy:	PoisonPolicy::Fail,
+    reset:	Some(reset),
+};
+
+/// Tells whether the test runs outside of [`run_child()`]; the child tests
+/// are skipped then
+fn is_parent() -> bool {
+    std::env::var_os("ETEST_CHILD").is_none()
+}
+
+// the children are run in alphabetical order by a single thread
+
+#[etest(consumes=[FAIL_DEV], skip=is_parent())]
+fn child_fail_0() {
+    panic!("broken");
+}
+
+#[etest(consumes=["poison-fail"], skip=is_parent())]
+fn child_fail_1() {
+    println!("RUNNING child_fail_1");
+}
+
+#[etest(uses=[SKIP_DEV], skip=is_parent())]
+fn child_skip_0() {
+    panic!("broken");
+}
+
+#[etest(consumes=[SKIP_DEV], skip=is_parent())]
+fn child_skip_1() {
+    panic!("broken");
+}
+
+#[etest(uses=[SKIP_DEV], skip=is_parent())]
+fn child_skip_2() {
+    println!("RUNNING child_skip_2");
+}
+
+#[etest(skip=is_parent())]
+fn child_skip_3() {
+    let _dev = etest::reserve().uses(SKIP_DEV).lock();
+
+    println!("RUNNING child_skip_3");
+}
+
+#[etest(consumes=[RESET_DEV], skip=is_parent(), timeout=1000)]
+fn child_reset_0() {
+    panic!("broken");
+}
+
+#[etest(consumes=[RESET_DEV], skip=is_parent())]
+fn child_reset_1() {
+    println!("RUNNING child_reset_1");
+}
+
+#[etest(consumes=[RESET_DEV], skip=is_parent())]
+fn child_reset_2() {
+    println!("RUNNING child_reset_2");
+}
+
+fn run_child(filter: &str) -> Output {
+    Command::new(std::env::current_exe().unwrap())
+        .env("ETEST_CHILD", "1")
+        .args([filter, "--nocapture", "--test-threads=1"])
+        .output()
+        .unwrap()
+}
+
+/// Returns whether test `name` passed; failed tests are listed at the end
+fn passed(output: &Output, name: &str) -> bool {
+    let stdout = String::from_utf8_lossy(&output.stdout);
+
+    assert!(stdout.contains(&format!("test {name} ...")));
+
+    !stdout.lines().any(|l| l == format!("    {name}"))
+}
+
+fn stdout_contains(output: &Output, pattern: &str) -> bool {
+    String::from_utf8_lossy(&output.stdout).contains(pattern)
+}
+
+/// Returns the tests which are named as poisoning ones
+fn poisoned_by(output: &Output) -> Vec<String> {
+    String::from_utf8_lossy(&output.stderr).lines()
+        .filter_map(|l| l.split_once(" has been poisoned by "))
+        .map(|(_, by)| by.rsplit(' ').next().unwrap().to_string())
+        .collect()
+}
+
+fn stderr_contains(output: &Output, pattern: &str) -> bool {
+    String::from_utf8_lossy(&output.stderr).contains(pattern)
+}
+
+#[test]
+fn test_0() {
+    let output = run_child("child_fail_");
+
+    assert!(!passed(&output, "child_fail_1"));
+    assert!(!stdout_contains(&output, "RUNNING child_fail_1"));
+    assert!(stderr_contains(&output, "(child_fail_1): resource Id(\"poison-fail\") has been poisoned by"));
+    assert_eq!(poisoned_by(&output), ["(child_fail_0)"]);
+}
+
+#[test]
+fn test_1() {
+    let output = run_child("child_skip_");
+
+    // only consumers poison the resource
+    assert!(!passed(&output, "child_skip_0"));
+    assert!(!passed(&output, "child_skip_1"));
+
+    assert!(passed(&output, "child_skip_2"));
+    assert!(!stdout_contains(&output, "RUNNING child_skip_2"));
+    assert!(stderr_contains(&output, "(child_skip_2): SKIPPED"));
+    assert_eq!(poisoned_by(&output), ["(child_skip_1)", "(child_skip_1)"]);
+
+    // reservations within the test can not skip it
+    assert!(!passed(&output, "child_skip_3"));
+    assert!(!stdout_contains(&output, "RUNNING child_skip_3"));
+}
+
+#[test]
+fn test_2() {
+    let output = run_child("child_reset_");
+
+    // the first reset fails, the second one succeeds
+    assert!(!passed(&output, "child_reset_1"));
+    assert!(passed(&output, "child_reset_2"));
+    assert!(stdout_contains(&output, "RUNNING child_reset_2"));
+}
@@ -0,0 +1,85 @@
+//! Tests skipping of tests when resources are not available
+
+use std::sync::atomic::{ AtomicBool, Ordering };
+
+use etest::prelude::*;
+
+fn no() -> bool {
+    false
+}
+
+fn yes() -> bool {
+    true
+}
+
+static SETUP: AtomicBool = AtomicBool::new(false);
+static RAN: AtomicBool = AtomicBool::new(false);
+
+fn start_database() {
+    SETUP.store(true, Ordering::SeqCst);
+}
+
+const MISSING: Probe<&str> = Probe {
+    resource:		"probe-missing",
+    is_available:	no,
+};
+
+const PRESENT: Probe<&str> = Probe {
+    resource:		"probe-present",
+    is_available:	yes,
+};
+
+const DATABASE: Probe<Lifecycle<&str>> = Probe {
+    resource:		Lifecycle {
+        resource:	"probe-database",
+        setup:		start_database,
+        teardown:	|| {},
+    },
+    is_available:	no,
+};
+
+#[etest(consumes=[MISSING])]
+fn test_0() {
+    unreachable!();
+}
+
+#[etest(uses=[MISSING, PRESENT])]
+fn test_1() {
+    unreachable!();
+}
+
+#[etest(uses=[PRESENT])]
+fn test_2() {
+    RAN.store(true, Ordering::SeqCst);
+}
+
+#[etest(uses=[DATABASE])]
+fn test_3() {
+    unreachable!();
+}
+
+#[etest(consumes=[MISSING], skip_result=Ok(()))]
+fn test_4() -> Result<(), ()> {
+    Err(())
+}
+
+#[test]
+fn test_5() {
+    // reservations within a test can not skip it
+    let res = std::panic::catch_unwind(|| etest::reserve().uses(MISSING).lock());
+
+    assert!(res.is_err());
+
+    // the probe has been registered for the resource
+    let res = std::panic::catch_unwind(|| etest::reserve().uses("probe-missing").lock());
+
+    assert!(res.is_err());
+
+    let _guard = etest::reserve().uses(PRESENT).lock();
+}
+
+#[etest(no_default_uses, after=[test_2, test_3])]
+fn test_6() {
+    assert!(RAN.load(Ordering::SeqCst));
+    assert!(!SETUP.load(Ordering::SeqCst));
+}
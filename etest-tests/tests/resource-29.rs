@@ -0,0 +1,120 @@
+//! Tests rate limited resources
+
+use std::time::{ Duration, Instant };
+
+use etest::prelude::*;
+
+const RATE_0: RateLimit<&str> = RateLimit {
+    resource:	"rate-0",
+    starts:	3,
+    per:	Duration::from_millis(400),
+};
+
+const RATE_1: RateLimit<&str> = RateLimit {
+    resource:	"rate-1",
+    starts:	1,
+    per:	Duration::from_secs(60),
+};
+
+const RATE_2: RateLimit<&str> = RateLimit {
+    resource:	"rate-2",
+    starts:	1,
+    per:	Duration::from_secs(60),
+};
+
+const RATE_4: RateLimit<&str> = RateLimit {
+    resource:	"rate-4",
+    starts:	2,
+    per:	Duration::from_millis(300),
+};
+
+/// Returns the message of a caught panic
+fn panic_msg(err: Box<dyn std::any::Any + Send>) -> String {
+    match err.downcast::<String>() {
+        Ok(msg)	=> *msg,
+        Err(err)	=> err.downcast_ref::<&str>().unwrap().to_string(),
+    }
+}
+
+/// Checks that no more than `starts` of the sorted `times` are within a
+/// window of length `per`
+fn assert_rate(times: &[Instant], starts: usize, per: Duration) {
+    for w in times.windows(starts + 1) {
+        assert!(w[starts] - w[0] >= per, "{times:?}");
+    }
+}
+
+#[test]
+fn test_0() {
+    let start = Instant::now();
+    let mut times = Vec::new();
+
+    for _ in 0..4 {
+        let _guard = etest::reserve().uses(RATE_0).lock();
+
+        times.push(Instant::now());
+    }
+
+    // the first reservations are not delayed
+    assert!(times[2] - start < Duration::from_millis(300));
+    assert_rate(&times, 3, RATE_0.per);
+}
+
+// reservations of a test which holds the resource are not limited
+#[etest(uses=[RATE_1])]
+fn test_1() {
+    let start = Instant::now();
+
+    for _ in 0..3 {
+        let _guard = etest::reserve().uses("rate-1").lock();
+    }
+
+    assert!(start.elapsed() < Duration::from_secs(1));
+}
+
+#[test]
+fn test_2() {
+    drop(etest::reserve().uses(RATE_2).lock());
+
+    let res = std::panic::catch_unwind(|| etest::reserve()
+                                       .uses("rate-2")
+                                       .lock_timeout(Duration::from_millis(100))
+                                       .lock());
+
+    let msg = panic_msg(res.map(drop).unwrap_err());
+
+    assert!(msg.contains("TIMEOUT"), "{msg}");
+    assert!(msg.contains("rate limit"), "{msg}");
+}
+
+#[test]
+fn test_3() {
+    let res = std::panic::catch_unwind(|| etest::reserve()
+                                       .uses(RateLimit {
+                                           resource:	"rate-3",
+                                           starts:	0,
+                                           per:		Duration::from_secs(1),
+                                       })
+                                       .lock());
+
+    assert!(panic_msg(res.map(drop).unwrap_err()).contains("does not allow any start"));
+}
+
+#[test]
+fn test_4() {
+    // waiting reservations are woken up when the next token is available
+    let threads: Vec<_> = (0..5)
+        .map(|_| std::thread::spawn(|| {
+            let _guard = etest::reserve().uses(RATE_4).lock();
+
+            Instant::now()
+        }))
+        .collect();
+
+    let mut times: Vec<_> = threads.into_iter()
+        .map(|t| t.join().unwrap())
+        .collect();
+
+    times.sort();
+    assert_rate(&times, 2, RATE_4.per);
+}
@@ -1,4 +1,5 @@
 use crate::{ Location, Timeout };
+use crate::resource::TestContext;
 
 pub fn mark_skipped(loc: &Location) {
     eprintln!("{}: SKIPPED", loc);
@@ -37,8 +38,12 @@ where
 
     let handle = t_builder.spawn({
         let is_alive = Arc::downgrade(&is_alive);
+        // resources are reserved by the calling thread; let nested
+        // reservations know about them
+        let ctx = TestContext::current();
 
         move || {
+            let _ctx = ctx.map(TestContext::enter);
             let val = f();
 
             if is_alive.strong_count() > 0 {
@@ -10,6 +10,8 @@
 //!
 //! - [scheduling timeouts](#timeout) of tests
 //!
+//! - [ordering](#ordering) of tests
+//!
 //! See [etest-tests](../../etest_tests/) crate for more examples.
 //!
 //! ## Conditional execution
@@ -66,7 +68,7 @@
 //!
 //! Parallel execution of tests can be prevented by consuming resources for
 //! the runtime of the test.  A "resource" can be specified by something which
-//! implements [`Into<ResourceId>`](ResourceId).
+//! implements [`Into<ResourceId>`](type@ResourceId).
 //!
 //! Related attributes:
 //!
@@ -86,6 +88,14 @@
 //!   `no_default_uses` above) is consumed so that the test does not run with
 //!   other ones in parallel.
 //!
+//! - `consumes_any`: a list of resources of which only one is consumed;
+//!   whichever is available first.  The selected one can be queried by
+//!   [`resources::selected()`](resources::selected).
+//!
+//! - `priority`: an `i32` expression; when several tests are waiting for the
+//!   same resource, the one with the highest priority gets it first.  Tests
+//!   without this attribute have a priority of 0.
+//!
 //! Both the `uses` and `consumes` resources can be specified as
 //!
 //! - a single literal (e.g. `"video"`)
@@ -93,9 +103,81 @@
 //! - a bracket, comma separated list of expressions which are evaluated at
 //!   runtime of the test
 //!
+//! Expressions can be everything which implements [`ResourceRequest`]; this
+//! covers [`Into<ResourceId>`](type@ResourceId) and wrappers like [`Slots`] for
+//! resources which can be consumed by more than one test at the same time
+//! or [`Weight`] for tests which need more than one unit of such a resource.
+//! The conversion of custom enums and structs into resource ids can be
+//! derived by [`#[derive(ResourceId)]`](macro@ResourceId).
+//! Lists of resources which are needed by many tests can be declared once by
+//! [`resource_group!`].
+//! Values which are shared by tests can be guarded by a [`Resource`]; tests
+//! get access to them according to their reservations.
+//! Resources which must be prepared (e.g. a database server which is
+//! started by the first test and stopped after the last one) can be wrapped
+//! in [`Lifecycle`].
+//! Devices which might be left in a broken state by a failing test can be
+//! wrapped in [`PoisonOnFailure`]; later tests which request them fail or
+//! are skipped until they have been reset.
+//! Tests which need a resource that is not present on every test host (e.g.
+//! a video device) are skipped when the resource is wrapped in a [`Probe`]
+//! which tells that it is not available.
+//! Services which reject bursts of requests (e.g. a license server) can be
+//! wrapped in [`RateLimit`]; it limits the number of tests which start to
+//! hold the resource within a time window.
+//!
+//! Resources which differ between test hosts (e.g. the serial port of a
+//! device) can be taken from environment variables by [`env_resource()`].
+//!
+//! Resource ids can be hierarchical (e.g. `"usb/hub1/port3"`); requesting
+//! such a resource implies the shared use of its parents.  See
+//! [`ResourceId`](type@ResourceId).
+//!
 //! Resources will be allocated **after** checking whether test shall be
 //! skipped.
 //!
+//! Waiting tests are served by their `priority` and then in the order of
+//! their requests; e.g. a `notparallel` test is not overtaken by tests which
+//! start later and use the default resource.  This applies to threads which
+//! are spawned by a test, too: they do not share the resources of the test
+//! and their reservations queue behind waiting tests.  A test which waits
+//! for such a thread (e.g. by `join()`) while a waiting test needs a
+//! resource held by the test will hang; use `lock_timeout` for reservations
+//! in spawned threads.
+//!
+//! `#[etest]` functions which are called by a test share the resources of
+//! the calling test; a resource which is held already in a sufficient mode
+//! (e.g. consumed by the caller and used or consumed by the called function)
+//! is granted immediately and released when the outermost test finishes.
+//!
+//! Deadlocks (e.g. when a test calls an `#[etest]` function which consumes a
+//! resource used by the test, or when tests wait for each other's resources)
+//! are detected and cause a `panic!` which names the involved resources and
+//! tests.  Only waiting for resources is considered; a test which waits for
+//! something else (e.g. for a spawned thread as described above) is
+//! expected to release its resources eventually.
+//!
+//! Resources can be reserved for a part of a test by [`reserve()`]; e.g. when
+//! only a short section of a long running test needs a resource exclusively.
+//! Used resources of such a reservation can be upgraded to exclusive use by
+//! [`ResourceLockGuard::upgrade()`] and downgraded afterwards; the upgrade
+//! waits until the other users released them.
+//!
+//! `async` tests wait for their resources in a future which is woken when
+//! resources are released, so they do not block the worker threads of the
+//! runtime.  Use [`ResourceBuilder::lock_async()`] in `async` code instead of
+//! `lock()`.  A worker thread runs many tasks, so an `async` test enters its
+//! context only while its body is polled.  Nested reservations and
+//! `async` `#[etest]` functions which are created within the body share
+//! the resources of the test, and typed resources and
+//! [`resources::selected()`](resources::selected) work as in plain tests.
+//! Reservations outside of a test are separate test contexts; this
+//! includes futures which are spawned by the test as separate tasks.
+//!
+//! The current state of the resources (the tests which hold them and the
+//! ones which wait for them) can be inspected by
+//! [`resources::snapshot()`](resources::snapshot).
+//!
 //! ### Examples
 //!
 //! ```
@@ -134,6 +216,38 @@
 //! fn test2() { /* ... */ }
 //! ```
 //!
+//! ```
+//! # use etest::{ etest, Slots };
+//! // at most two tests run in parallel
+//! #[etest(consumes=[Slots("serial", 2)])]
+//! fn test3() { /* ... */ }
+//! ```
+//!
+//! ### Locking across processes
+//!
+//! Resources are managed per process by default; tests of different test
+//! binaries (or test runners like `cargo-nextest` which start a process per
+//! test) are not serialized.
+//!
+//! With the `flock` feature (unix only), resources are additionally locked
+//! by `flock(2)` on files in a lock directory; consumed resources
+//! exclusively, used ones shared.  The directory can be set by the
+//! `ETEST_LOCK_DIR` environment variable and defaults to `etest-locks` in
+//! the temporary directory.
+//!
+//! Capacity and weights of resources are accounted only within a process; a
+//! resource which is consumed by tests of one process can not be reserved
+//! by other ones.  Deadlock detection and `priority` do not cover other
+//! processes either.
+//!
+//! ### Contention statistics
+//!
+//! With the `stats` feature, the time which tests waited for resources and
+//! held them is recorded and a summary is written when the test process
+//! exits.  Waiting time is accounted to the resources which blocked the
+//! test.  The summary is written to stderr or appended to the file given by
+//! the `ETEST_STATS_FILE` environment variable.
+//!
 //! ## Timeout
 //!
 //! Related attributes:
@@ -147,6 +261,14 @@
 //!
 //! Clock will start to tick **after** resources have been allocated.
 //!
+//! - `lock_timeout`: timeout for reserving the resources.  When they are
+//!   not available after this time, the test will be aborted by a `panic!`
+//!   which lists the blocking resources together with the tests holding
+//!   them.
+//!
+//!   When not given, the value of the `ETEST_LOCK_TIMEOUT` environment
+//!   variable (in milliseconds) is used.  Without it, the test waits forever.
+//!
 //! ### Examples
 //!
 //! ```
@@ -154,6 +276,54 @@
 //! #[etest(timeout=20_000)]
 //! fn test() { /* ... */ }
 //! ```
+//!
+//! ```
+//! # use etest::etest;
+//! #[etest(consumes="video", lock_timeout=60_000, timeout=20_000)]
+//! fn test() { /* ... */ }
+//! ```
+//!
+//! ## Ordering
+//!
+//! Related attributes:
+//!
+//! - `after`: a bracket, comma separated list of paths to tests which must
+//!   have completed before the test starts
+//!
+//! - `before`: a bracket, comma separated list of paths to tests which do
+//!   not start before the test completed
+//!
+//! Paths are relative to the module of the test (e.g. `super::setup`) and
+//! must name `#[etest]` tests.  Only tests which are run in the same process
+//! are waited for; tests which are filtered out by the command line of the
+//! test (or which are ignored) do not delay other ones.  Failed and skipped
+//! tests count as completed.
+//!
+//! The ordering is checked after the `skip` condition and before resources
+//! are allocated; skipped tests do not wait for other ones.
+//!
+//! ### Limitations
+//!
+//! A waiting test occupies a thread of the test harness.  The harness starts
+//! tests in alphabetical order; tests should be named so that their
+//! dependencies come first.  Otherwise, the dependencies might not start
+//! when all threads are occupied by waiting tests (e.g. with
+//! `--test-threads=1`); the waiting tests are aborted by a `panic!` then.
+//! Cyclic constraints cause a `panic!` too.
+//!
+//! Tests are registered at startup of the process which is supported on
+//! Linux and other ELF platforms, macOS and Windows.
+//!
+//! ### Examples
+//!
+//! ```
+//! # use etest::etest;
+//! #[etest]
+//! fn test_0_provision() { /* ... */ }
+//!
+//! #[etest(after=[test_0_provision])]
+//! fn test_1_verify() { /* ... */ }
+//! ```
 
 
 // declares macros for use in crate; must be on top of file
@@ -162,11 +332,15 @@ mod logging;
 
 pub use etest_derive::etest;
 
+/// Derives the conversion of enums and structs into a [`ResourceId`](type@ResourceId)
+pub use etest_derive::ResourceId;
+
 mod resource;
 mod location;
 mod timeout;
 mod default_return;
 mod helpers;
+mod order;
 
 #[doc(hidden)]
 pub use location::Location;
@@ -178,8 +352,28 @@ pub use resource::ResourceId;
 // documentation
 pub use resource::ResourceIdImpl;
 
+#[doc(inline)]
+pub use resource::typed::Resource;
+
+#[doc(inline)]
+pub use resource::{ Lifecycle, PoisonOnFailure, PoisonPolicy, Probe, RateLimit, ResourceGroup, ResourceRequest,
+                    Slots, Weight };
+
+#[doc(inline)]
+pub use resource::{ reserve, ReserveFuture, ResourceBuilder, ResourceLockGuard };
+
+#[doc(inline)]
+pub use resource::{ env_resource, path_resource };
+
 #[doc(hidden)]
-pub use resource::{ ResourceBuilder, RESOURCES };
+pub use resource::{ ResourceItem, RESOURCES };
+
+/// Introspection of the managed resources
+pub mod resources {
+    pub use crate::resource::snapshot::{ snapshot, Snapshot, ResourceSnapshot,
+                                         HolderSnapshot, WaiterSnapshot };
+    pub use crate::resource::selected;
+}
 
 #[doc(inline)]
 pub use default_return::DefaultReturn;
@@ -190,10 +384,22 @@ pub use timeout::Timeout;
 #[doc(hidden)]
 pub use helpers::*;
 
+#[doc(hidden)]
+pub use order::{ OrderFuture, TestOrder };
+
 #[doc(hidden)]
 pub mod prelude {
     pub use crate::DefaultReturn;
+    pub use crate::Lifecycle;
+    pub use crate::PoisonOnFailure;
+    pub use crate::PoisonPolicy;
+    pub use crate::Probe;
+    pub use crate::RateLimit;
+    pub use crate::Resource;
     pub use crate::ResourceId;
+    pub use crate::resource_group;
+    pub use crate::Slots;
     pub use crate::Timeout;
+    pub use crate::Weight;
     pub use crate::etest;
 }
@@ -1,6 +1,9 @@
 #[derive(Debug, Clone, Hash, Eq, PartialEq)]
 pub struct Location {
     loc:	std::panic::Location<'static>,
+    /// name of the thread which runs the test; locations are displayed by
+    /// other threads too (e.g. in resource snapshots)
+    thread:	Option<String>,
 }
 
 impl Location {
@@ -9,6 +12,7 @@ impl Location {
     pub fn new() -> Self {
         Self {
             loc: *std::panic::Location::caller(),
+            thread: std::thread::current().name().map(String::from),
         }
     }
 }
@@ -17,7 +21,7 @@ impl std::fmt::Display for Location {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         self.loc.fmt(f)?;
 
-        if let Some(name) = std::thread::current().name() {
+        if let Some(name) = &self.thread {
             " (".fmt(f)?;
             name.fmt(f)?;
             ")".fmt(f)?;
@@ -0,0 +1,403 @@
+//! Ordering of tests by their `after` and `before` constraints
+//!
+//! Tests are registered at process start by [`register_test!`]; this allows
+//! to tell whether a test which is named by a constraint will run in this
+//! process at all.  A test waits in [`TestOrder::start()`] until the tests
+//! which must run before it have completed.
+
+use std::collections::{ HashMap, HashSet };
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::{ Condvar, Mutex, MutexGuard, PoisonError };
+use std::task::{ Context, Poll, Waker };
+
+use once_cell::sync::Lazy;
+
+/// Registers a test for being ordered against other ones
+///
+/// Expanded by `#[etest]` within the test function.  The registration runs
+/// as a constructor before `main()`.
+#[doc(hidden)]
+#[macro_export]
+macro_rules! register_test {
+    ($name:literal, $ignored:literal, [$($before:literal),* $(,)?]) => {
+        #[used]
+        #[cfg_attr(any(target_os = "linux", target_os = "android",
+                       target_os = "freebsd", target_os = "netbsd",
+                       target_os = "openbsd", target_os = "dragonfly",
+                       target_os = "illumos", target_os = "solaris"),
+                   link_section = ".init_array")]
+        #[cfg_attr(any(target_os = "macos", target_os = "ios"),
+                   link_section = "__DATA,__mod_init_func")]
+        #[cfg_attr(windows, link_section = ".CRT$XCU")]
+        static ETEST_REGISTER_TEST: extern "C" fn() = {
+            extern "C" fn register() {
+                $crate::TestOrder::register(module_path!(), $name, $ignored, &[$($before),*]);
+            }
+
+            register
+        };
+    };
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum RunIgnored {
+    No,
+    Yes,
+    Only,
+}
+
+/// The tests which are selected by the command line of the test harness
+#[derive(Debug)]
+struct Filter {
+    filters:	Vec<String>,
+    skip:	Vec<String>,
+    exact:	bool,
+    ignored:	RunIgnored,
+    threads:	Option<usize>,
+}
+
+impl Filter {
+    /// Parses the options of the `libtest` harness which select tests
+    fn from_args(mut args: impl Iterator<Item = String>) -> Self {
+        let mut res = Self {
+            filters:	Vec::new(),
+            skip:	Vec::new(),
+            exact:	false,
+            ignored:	RunIgnored::No,
+            threads:	None,
+        };
+
+        while let Some(arg) = args.next() {
+            let (opt, value) = match arg.split_once('=') {
+                Some((o, v)) if o.starts_with("--")	=> (o.to_string(), Some(v.to_string())),
+                _					=> (arg, None),
+            };
+
+            match opt.as_str() {
+                "--exact"		=> res.exact = true,
+                "--ignored"		=> res.ignored = RunIgnored::Only,
+                "--include-ignored"	=> res.ignored = RunIgnored::Yes,
+
+                "--skip"	=> res.skip.extend(value.or_else(|| args.next())),
+                "--test-threads"	=> res.threads = value.or_else(|| args.next())
+                    .and_then(|v| v.parse().ok()),
+
+                // options with a value which must not be taken as filter
+                "--logfile" | "--format" | "--color" | "--shuffle-seed" | "-Z"	=> {
+                    if value.is_none() {
+                        args.next();
+                    }
+                },
+
+                o if o.starts_with('-')	=> {},
+                _			=> res.filters.push(opt),
+            }
+        }
+
+        res
+    }
+
+    fn matches(&self, name: &str, pattern: &str) -> bool {
+        match self.exact {
+            true	=> name == pattern,
+            false	=> name.contains(pattern),
+        }
+    }
+
+    /// Tells whether test `name` is run by the harness
+    fn selects(&self, name: &str, ignored: bool) -> bool {
+        let ignore_ok = match self.ignored {
+            RunIgnored::No	=> !ignored,
+            RunIgnored::Yes	=> true,
+            RunIgnored::Only	=> ignored,
+        };
+
+        ignore_ok &&
+            (self.filters.is_empty() || self.filters.iter().any(|f| self.matches(name, f))) &&
+            !self.skip.iter().any(|s| self.matches(name, s))
+    }
+
+    /// Returns the number of tests which are run in parallel
+    fn concurrency(&self) -> usize {
+        self.threads
+            .or_else(|| std::env::var("RUST_TEST_THREADS").ok()?.parse().ok())
+            .or_else(|| std::thread::available_parallelism().ok().map(usize::from))
+            .unwrap_or(1)
+    }
+}
+
+#[derive(Default)]
+struct State {
+    /// registered tests together with a flag whether they are ignored
+    registered:	HashMap<String, bool>,
+    /// tests which must complete before a test starts
+    deps:	HashMap<String, HashSet<String>>,
+    completed:	HashSet<String>,
+    /// number of tests which wait for other ones
+    waiting:	usize,
+    wakers:	Vec<Waker>,
+}
+
+struct Order {
+    state:	Mutex<State>,
+    notify:	Condvar,
+}
+
+static ORDER: Lazy<Order> = Lazy::new(|| Order {
+    state:	Mutex::new(State::default()),
+    notify:	Condvar::new(),
+});
+
+static FILTER: Lazy<Filter> = Lazy::new(|| Filter::from_args(std::env::args().skip(1)));
+
+fn state() -> MutexGuard<'static, State> {
+    // a panic while holding the lock does not corrupt the state
+    ORDER.state.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+/// Returns the name of test `path` as shown by the test harness; `path` is
+/// relative to `module` which is the result of `module_path!()`
+fn resolve(module: &str, path: &str) -> String {
+    // the harness omits the crate name
+    let mut res: Vec<&str> = module.split("::").skip(1).collect();
+
+    for seg in path.trim_start_matches("::").split("::") {
+        match seg {
+            "self"	=> {},
+            "super"	=> { res.pop(); },
+            "crate"	=> res.clear(),
+            s		=> res.push(s),
+        }
+    }
+
+    res.join("::")
+}
+
+impl State {
+    /// Returns the dependencies of `test` which will run but have not
+    /// completed yet
+    fn pending(&self, test: &str) -> Result<Vec<&str>, String> {
+        let mut res = Vec::new();
+
+        for dep in self.deps.get(test).into_iter().flatten() {
+            let Some(ignored) = self.registered.get(dep) else {
+                return Err(format!("'{dep}' is not a test declared by #[etest]"));
+            };
+
+            if !self.completed.contains(dep) && FILTER.selects(dep, *ignored) {
+                res.push(dep.as_str());
+            }
+        }
+
+        Ok(res)
+    }
+
+    /// Returns a cycle of pending dependencies which starts at `test`
+    fn find_cycle(&self, test: &str) -> Option<Vec<String>> {
+        let mut stack = vec![(test.to_string(), vec![test.to_string()])];
+        let mut seen = HashSet::new();
+
+        while let Some((t, path)) = stack.pop() {
+            for dep in self.pending(&t).unwrap_or_default() {
+                if dep == test {
+                    let mut cycle = path.clone();
+
+                    cycle.push(dep.to_string());
+                    return Some(cycle);
+                }
+
+                if seen.insert(dep.to_string()) {
+                    let mut path = path.clone();
+
+                    path.push(dep.to_string());
+                    stack.push((dep.to_string(), path));
+                }
+            }
+        }
+
+        None
+    }
+
+    /// Tells whether `test` can start; fails when it would wait forever.
+    /// `counted` tells whether the test is accounted as waiting already.
+    fn check(&self, test: &str, counted: bool) -> Result<bool, String> {
+        if self.pending(test)?.is_empty() {
+            return Ok(true);
+        }
+
+        if let Some(cycle) = self.find_cycle(test) {
+            return Err(format!("ordering cycle {}", cycle.join(" -> ")));
+        }
+
+        // tests occupy a thread of the harness while they wait; the ones
+        // which they wait for can not start when all threads are occupied
+        let threads = FILTER.concurrency();
+
+        if self.waiting + usize::from(!counted) >= threads {
+            return Err(format!("all {threads} test threads wait for tests which did not start; \
+                                the harness starts tests in alphabetical order"));
+        }
+
+        Ok(false)
+    }
+
+    fn complete(&mut self, test: &str) {
+        self.completed.insert(test.to_string());
+
+        for w in self.wakers.drain(..) {
+            w.wake();
+        }
+
+        ORDER.notify.notify_all();
+    }
+}
+
+/// Marks the test as completed when dropped
+#[doc(hidden)]
+pub struct TestOrder {
+    name:	String,
+}
+
+impl TestOrder {
+    /// Registers test `name` of `module`; `before` are the tests which must
+    /// not start before it completed.
+    ///
+    /// Called before `main()`; must not panic.
+    pub fn register(module: &str, name: &str, ignored: bool, before: &[&str]) {
+        let name = resolve(module, name);
+        let mut state = state();
+
+        for b in before {
+            state.deps.entry(resolve(module, b)).or_default().insert(name.clone());
+        }
+
+        state.registered.insert(name, ignored);
+    }
+
+    fn new(module: &str, name: &str, after: &[&str]) -> Self {
+        let name = resolve(module, name);
+        let mut state = state();
+        let deps = state.deps.entry(name.clone()).or_default();
+
+        deps.extend(after.iter().map(|a| resolve(module, a)));
+
+        Self {
+            name:	name,
+        }
+    }
+
+    /// Fails the test because it would wait forever
+    fn fail(self, state: MutexGuard<'_, State>, msg: String) -> ! {
+        let name = self.name.clone();
+
+        // tests waiting for this one can proceed now
+        drop(state);
+        drop(self);
+
+        panic!("{name}: ORDER DEADLOCK: {msg}");
+    }
+
+    /// Waits until the tests which must run before test `name` of `module`
+    /// completed; `after` are the tests given by the test itself.
+    pub fn start(module: &str, name: &str, after: &[&str]) -> Self {
+        let res = Self::new(module, name, after);
+        let mut state = state();
+        let mut waiting = false;
+
+        loop {
+            match state.check(&res.name, waiting) {
+                Ok(true)	=> break,
+                Ok(false)	=> {},
+                Err(e)		=> {
+                    state.waiting -= usize::from(waiting);
+                    res.fail(state, e);
+                },
+            }
+
+            if !waiting {
+                state.waiting += 1;
+                waiting = true;
+            }
+
+            state = ORDER.notify.wait(state).unwrap_or_else(PoisonError::into_inner);
+        }
+
+        state.waiting -= usize::from(waiting);
+
+        res
+    }
+
+    /// Marks test `name` of `module` as completed without starting it;
+    /// called when the test has been skipped
+    pub fn skipped(module: &str, name: &str) {
+        state().complete(&resolve(module, name));
+    }
+
+    /// Like [`start()`](Self::start()) but waits without blocking the
+    /// thread
+    pub fn start_async(module: &str, name: &str, after: &[&str]) -> OrderFuture {
+        OrderFuture {
+            order:	Some(Self::new(module, name, after)),
+            waiting:	false,
+        }
+    }
+}
+
+impl std::ops::Drop for TestOrder {
+    fn drop(&mut self) {
+        state().complete(&self.name);
+    }
+}
+
+/// Resolves to the [`TestOrder`] of a test when it can start
+#[doc(hidden)]
+pub struct OrderFuture {
+    order:	Option<TestOrder>,
+    /// whether the test has been accounted as waiting
+    waiting:	bool,
+}
+
+impl Future for OrderFuture {
+    type Output = TestOrder;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let order = self.order.take().expect("polled after completion");
+        let mut state = state();
+
+        match state.check(&order.name, self.waiting) {
+            Ok(true)	=> {
+                state.waiting -= usize::from(self.waiting);
+                self.waiting = false;
+                Poll::Ready(order)
+            },
+
+            Ok(false)	=> {
+                if !self.waiting {
+                    state.waiting += 1;
+                    self.waiting = true;
+                }
+
+                state.wakers.push(cx.waker().clone());
+                drop(state);
+
+                self.order = Some(order);
+                Poll::Pending
+            },
+
+            Err(e)	=> {
+                state.waiting -= usize::from(self.waiting);
+                self.waiting = false;
+                order.fail(state, e);
+            },
+        }
+    }
+}
+
+impl std::ops::Drop for OrderFuture {
+    fn drop(&mut self) {
+        if self.waiting {
+            state().waiting -= 1;
+        }
+    }
+}
@@ -1,23 +1,198 @@
-use std::collections::HashSet;
 use std::sync::{ Arc, RwLock };
 
 use crate::Location;
 
 use super::{ ResourceId, ResourceEntry };
+use super::lifecycle::Hooks;
+use super::poison::Poison;
+use super::rate::RateLimiter;
 
-#[derive(Debug, Eq, PartialEq)]
+/// A reservation of some units of a resource
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Holder {
+    /// unique id of the reservation
+    pub(super) id:	u64,
+    /// id of the [`TestContext`](super::TestContext)
+    pub(super) ctx:	u64,
+    pub(super) loc:	Location,
+    pub(super) units:	usize,
+    /// number of nested reservations which share this one
+    pub(super) refs:	usize,
+}
+
+#[derive(Debug)]
 pub struct Resource {
     pub id:		ResourceId,
-    pub(super) owner:	Option<Location>,
-    pub(super) users:	HashSet<Location>,
+    pub(super) capacity:	Option<usize>,
+    pub(super) owners:	Vec<Holder>,
+    pub(super) users:	Vec<Holder>,
+    /// setup and teardown callbacks
+    pub(super) hooks:	Option<Arc<Hooks>>,
+    /// poisoning state when the resource has the `PoisonOnFailure` policy
+    pub(super) poison:	Option<Arc<Poison>>,
+    /// tells whether the resource is available on this host
+    pub(super) probe:	Option<fn() -> bool>,
+    /// limits the number of reservations per time window
+    pub(super) rate:	Option<RateLimiter>,
+    /// lock which is held by this process while the resource is in use
+    #[cfg(feature = "flock")]
+    pub(super) process_lock:	Option<super::flock::ProcessLock>,
+}
+
+fn sum_units<'a>(holders: impl Iterator<Item = &'a Holder>) -> usize {
+    holders.map(|h| h.units).sum()
+}
+
+/// Drops a reference to the holder of reservation `id` and removes it when
+/// it was the last one; returns whether holder has been removed.
+pub(super) fn release_holder(holders: &mut Vec<Holder>, id: u64) -> bool {
+    let Some(pos) = holders.iter().position(|h| h.id == id) else {
+        return false;
+    };
+
+    holders[pos].refs -= 1;
+
+    if holders[pos].refs > 0 {
+        return false;
+    }
+
+    holders.swap_remove(pos);
+    true
 }
 
 impl Resource {
     pub fn new(id: &ResourceId) -> Self {
         Self {
             id:		id.clone(),
-            owner:	None,
-            users:	HashSet::new(),
+            capacity:	None,
+            owners:	Vec::new(),
+            users:	Vec::new(),
+            hooks:	None,
+            poison:	None,
+            probe:	None,
+            rate:	None,
+            #[cfg(feature = "flock")]
+            process_lock:	None,
+        }
+    }
+
+    /// Returns the number of units which can be held at the same time
+    pub fn capacity(&self) -> usize {
+        self.capacity.unwrap_or(1)
+    }
+
+    pub fn set_capacity(&mut self, capacity: usize) -> Result<(), String> {
+        match self.capacity {
+            _ if capacity == 0	=>
+                Err(format!("resource {:?} declared without slots", self.id)),
+
+            Some(c) if c != capacity	=>
+                Err(format!("resource {:?} declared with {capacity} slots but has {c}", self.id)),
+
+            _	=> {
+                self.capacity = Some(capacity);
+                Ok(())
+            }
+        }
+    }
+
+    pub fn check_units(&self, units: usize) -> Result<(), String> {
+        match self.capacity() {
+            c if units > c	=>
+                Err(format!("{units} units of resource {:?} requested but it has only {c}", self.id)),
+            _	=> Ok(()),
+        }
+    }
+
+    /// Checks whether resource can be consumed when only the holders
+    /// matched by `counts` are considered
+    pub fn can_consume_if<F>(&self, units: usize, counts: F) -> bool
+    where
+        F: Fn(&Holder) -> bool,
+    {
+        !self.users.iter().any(&counts) &&
+            sum_units(self.owners.iter().filter(|h| counts(h))) + units <= self.capacity()
+    }
+
+    /// Checks whether resource can be used when only the holders matched by
+    /// `counts` are considered
+    pub fn can_use_if<F>(&self, units: usize, counts: F) -> bool
+    where
+        F: Fn(&Holder) -> bool,
+    {
+        !self.owners.iter().any(&counts) &&
+            sum_units(self.users.iter().filter(|h| counts(h))) + units <= self.capacity()
+    }
+
+    pub fn can_reserve_if<F>(&self, consume: bool, units: usize, counts: F) -> bool
+    where
+        F: Fn(&Holder) -> bool,
+    {
+        match consume {
+            true	=> self.can_consume_if(units, counts),
+            false	=> self.can_use_if(units, counts),
+        }
+    }
+
+    pub fn can_reserve(&self, consume: bool, units: usize) -> bool {
+        self.can_reserve_if(consume, units, |_| true)
+    }
+
+    /// Returns the reservation of the test context `ctx` which covers the
+    /// request; nested reservations can share it then.
+    pub fn covering_holder(&self, ctx: u64, consume: bool, units: usize) -> Option<u64> {
+        let users = match consume {
+            true	=> &[][..],
+            false	=> &self.users[..],
+        };
+
+        self.owners.iter()
+            .chain(users)
+            .find(|h| h.ctx == ctx && h.units >= units)
+            .map(|h| h.id)
+    }
+
+    /// Adds a reference to the holder of reservation `id`
+    pub fn share_holder(&mut self, id: u64) {
+        let holder = self.owners.iter_mut()
+            .chain(self.users.iter_mut())
+            .find(|h| h.id == id)
+            .unwrap();
+
+        holder.refs += 1;
+    }
+
+    /// Turns the user of reservation `id` into an owner of `units` units;
+    /// returns the units which it used before
+    pub fn upgrade_holder(&mut self, id: u64, units: usize) -> usize {
+        let pos = self.users.iter().position(|h| h.id == id).unwrap();
+        let holder = self.users.swap_remove(pos);
+        let prev = holder.units;
+
+        self.owners.push(Holder { units: units, ..holder });
+
+        prev
+    }
+
+    /// Turns the owner of reservation `id` back into a user of `units`
+    /// units
+    pub fn downgrade_holder(&mut self, id: u64, units: usize) {
+        let pos = self.owners.iter().position(|h| h.id == id).unwrap();
+        let holder = self.owners.swap_remove(pos);
+
+        self.users.push(Holder { units: units, ..holder });
+    }
+
+    pub fn holders(&self) -> impl Iterator<Item = &Holder> {
+        self.owners.iter().chain(self.users.iter())
+    }
+
+    /// Unlocks the resource for other processes when it is not held
+    /// anymore by this one
+    #[cfg(feature = "flock")]
+    pub fn release_process_lock(&mut self) {
+        if self.holders().next().is_none() {
+            self.process_lock = None;
         }
     }
 }
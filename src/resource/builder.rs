@@ -1,56 +1,193 @@
-use std::collections::HashSet;
+use std::collections::HashMap;
 use std::sync::RwLock;
 
-use crate::Location;
+use crate::{ Location, Timeout };
 
-use super::{ ResourceId, ResourceLockGuard, ResourceManager, ResourceSet };
+use super::{ ResourceId, ResourceItem, ResourceLockGuard, ResourceManager, ResourceRequest, ResourceSet,
+             ReserveFuture, RESOURCES };
 
+/// Reserves resources within a test
+///
+/// Created by [`reserve()`](crate::reserve); the resources are held until the
+/// guard which is returned by [`lock()`](Self::lock) is dropped.  This
+/// allows to hold a resource only for the critical section of a test.
+///
+/// ```
+/// # use etest::etest;
+/// #[etest]
+/// fn test() {
+///     /* ... */
+///
+///     {
+///         let _flash = etest::reserve().consumes("flash").lock();
+///         /* ... */
+///     }
+///
+///     /* ... */
+/// }
+/// ```
+///
+/// Reservations within a test are nested ones; they share the resources of
+/// the test and take part in the deadlock detection.
 pub struct ResourceBuilder {
-    uses:	HashSet<ResourceId>,
-    consumes:	HashSet<ResourceId>,
+    uses:	HashMap<ResourceId, ResourceItem>,
+    consumes:	HashMap<ResourceId, ResourceItem>,
+    consumes_any:	Vec<ResourceItem>,
+    lock_timeout:	Option<Timeout>,
+    priority:	i32,
 }
 
 impl ResourceBuilder {
     #[allow(clippy::new_without_default)]
     pub fn new() -> Self {
         Self {
-            uses:	HashSet::default(),
-            consumes:	HashSet::default(),
+            uses:	HashMap::default(),
+            consumes:	HashMap::default(),
+            consumes_any:	Vec::new(),
+            lock_timeout:	None,
+            priority:	0,
         }
     }
 
-    pub fn consumes<T: Into<ResourceId>>(mut self, id: T) -> Self {
-        match id.into() {
-            id if id.is_some()	=> self.consumes.insert(id),
-            _			=> false,
-        };
+    fn add(set: &mut HashMap<ResourceId, ResourceItem>, req: impl ResourceRequest) {
+        use std::collections::hash_map::Entry as E;
 
+        for item in req.into_items() {
+            if !item.id.is_some() {
+                continue;
+            }
+
+            match set.entry(item.id.clone()) {
+                E::Occupied(mut e)	=> e.get_mut().merge(item),
+                E::Vacant(v)		=> { v.insert(item); },
+            }
+        }
+    }
+
+    /// Adds resources which are consumed (exclusively)
+    pub fn consumes<T: ResourceRequest>(mut self, req: T) -> Self {
+        Self::add(&mut self.consumes, req);
         self
     }
 
-    pub fn uses<T: Into<ResourceId>>(mut self, id: T) -> Self {
-        match id.into() {
-            id if id.is_some()	=> self.uses.insert(id),
-            _			=> false,
-        };
+    /// Adds resources which are used (shared)
+    pub fn uses<T: ResourceRequest>(mut self, req: T) -> Self {
+        Self::add(&mut self.uses, req);
+        self
+    }
 
+    /// Adds candidates of which only one will be consumed; the first one
+    /// which is available is taken.
+    pub fn consumes_any<T: ResourceRequest>(mut self, req: T) -> Self {
+        self.consumes_any.extend(req.into_items()
+                                 .into_iter()
+                                 .filter(|item| item.id.is_some()));
         self
     }
 
+    /// Sets the maximum time for waiting on the resources.
+    ///
+    /// When not set, the value of the `ETEST_LOCK_TIMEOUT` environment
+    /// variable (in milliseconds) is used.  Without it, the reservation waits
+    /// forever.
+    pub fn lock_timeout<T: Into<Timeout>>(mut self, timeout: T) -> Self {
+        self.lock_timeout = Some(timeout.into());
+        self
+    }
+
+    /// Sets the priority of the reservation.
+    ///
+    /// When several tests are waiting for a resource, the one with the
+    /// highest priority will get it first.  Default is 0.
+    pub fn priority(mut self, priority: i32) -> Self {
+        self.priority = priority;
+        self
+    }
+
+    #[doc(hidden)]
     pub fn finish(mut self) -> ResourceSet {
-        for r in &self.consumes {
-            self.uses.remove(r);
+        // requesting a hierarchical resource implies the shared use of its
+        // parents
+        let parents: Vec<_> = self.consumes.keys()
+            .chain(self.uses.keys())
+            .flat_map(|id| id.parents())
+            .collect();
+
+        for id in parents {
+            Self::add(&mut self.uses, id);
+        }
+
+        for (id, item) in &mut self.consumes {
+            if let Some(u) = self.uses.remove(id) {
+                item.merge(u);
+            }
         }
 
+        // candidates which are requested explicitly would be reserved twice
+        let consumes_any: Vec<_> = self.consumes_any.into_iter()
+            .filter(|item| !self.consumes.contains_key(&item.id) && !self.uses.contains_key(&item.id))
+            .collect();
+
+        // the parents of a candidate are used only when it is selected
+        let any_parents = consumes_any.iter()
+            .map(|item| {
+                let parents = item.id.parents().into_iter()
+                    .filter(|p| !self.consumes.contains_key(p) && !self.uses.contains_key(p))
+                    .collect();
+
+                (item.id.clone(), parents)
+            })
+            .collect();
+
         ResourceSet {
             uses:	self.uses,
             consumes:	self.consumes,
+            consumes_any:	consumes_any,
+            any_parents:	any_parents,
+            priority:	self.priority,
         }
     }
 
-    pub fn reserve(self, manager: &RwLock<ResourceManager>, owner: &Location) -> ResourceLockGuard {
+    #[doc(hidden)]
+    pub fn reserve(self, manager: &'static RwLock<ResourceManager>, owner: &Location) -> ResourceLockGuard {
+        let timeout = self.lock_timeout.or_else(|| Timeout::from_env("ETEST_LOCK_TIMEOUT"));
+        let set = self.finish();
+
+        ResourceManager::reserve(manager, set, owner, timeout)
+    }
+
+    #[doc(hidden)]
+    pub fn reserve_async(self, manager: &'static RwLock<ResourceManager>, owner: &Location) -> ReserveFuture {
+        let timeout = self.lock_timeout.or_else(|| Timeout::from_env("ETEST_LOCK_TIMEOUT"));
         let set = self.finish();
 
-        ResourceManager::reserve(manager, set, owner)
+        ReserveFuture::new(manager, ResourceManager::start(manager, set, owner, timeout, true))
     }
+
+    /// Reserves the resources; waits until they are available
+    ///
+    /// Panics on deadlocks, when the `lock_timeout` expired or when a
+    /// resource has been poisoned (see [`PoisonOnFailure`](crate::PoisonOnFailure))
+    /// or is not available (see [`Probe`](crate::Probe)).
+    #[track_caller]
+    pub fn lock(self) -> ResourceLockGuard {
+        self.reserve(&RESOURCES, &Location::new()).fail_skipped()
+    }
+
+    /// Reserves the resources in async code
+    ///
+    /// Unlike [`lock()`](Self::lock), waiting for the resources does not
+    /// block the thread.
+    #[track_caller]
+    pub fn lock_async(self) -> ReserveFuture {
+        let mut res = self.reserve_async(&RESOURCES, &Location::new());
+
+        res.can_skip = false;
+        res
+    }
+}
+
+/// Starts a reservation of resources; see [`ResourceBuilder`]
+pub fn reserve() -> ResourceBuilder {
+    ResourceBuilder::new()
 }
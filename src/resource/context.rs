@@ -0,0 +1,142 @@
+use std::cell::RefCell;
+use std::sync::{ Arc, Mutex };
+use std::sync::atomic::{ AtomicBool, AtomicU64, Ordering };
+
+use super::ResourceId;
+
+/// The logical test which reserves resources
+///
+/// A context is created by the outermost reservation and is inherited by
+/// nested `#[etest]` functions and the worker thread which is spawned for
+/// the `timeout` handling.
+#[derive(Debug)]
+pub struct TestContext {
+    pub(crate) id:	u64,
+    /// the resources which have been selected for `consumes_any`, together
+    /// with the id of the reservation
+    selected:	Mutex<Vec<(u64, ResourceId)>>,
+}
+
+/// A context which has been entered by a thread
+struct Entered {
+    ctx:	Arc<TestContext>,
+    /// cleared when the [`ContextGuard`] is dropped
+    active:	Arc<AtomicBool>,
+}
+
+thread_local! {
+    /// the entered contexts; the innermost one last
+    static CURRENT: RefCell<Vec<Entered>> = const { RefCell::new(Vec::new()) };
+}
+
+/// Leaves the context when dropped
+///
+/// The guard can be dropped by another thread (e.g. together with a
+/// [`ResourceLockGuard`](super::ResourceLockGuard) which has been moved
+/// there); the context is left by the thread which entered it then too.
+pub struct ContextGuard {
+    active:	Arc<AtomicBool>,
+}
+
+impl TestContext {
+    fn new() -> Self {
+        static ID: AtomicU64 = AtomicU64::new(0);
+
+        Self {
+            id:		ID.fetch_add(1, Ordering::Relaxed),
+            selected:	Mutex::new(Vec::new()),
+        }
+    }
+
+    pub fn current() -> Option<Arc<Self>> {
+        CURRENT.with(|c| {
+            let mut entered = c.borrow_mut();
+
+            entered.retain(|e| e.active.load(Ordering::Acquire));
+            entered.last().map(|e| e.ctx.clone())
+        })
+    }
+
+    /// Returns the context of the current thread or creates a new one which
+    /// will be active until the returned guard is dropped.
+    pub fn current_or_new() -> (Arc<Self>, Option<ContextGuard>) {
+        match Self::current() {
+            Some(ctx)	=> (ctx, None),
+            None	=> {
+                let ctx = Arc::new(Self::new());
+                let guard = Self::enter(ctx.clone());
+
+                (ctx, Some(guard))
+            }
+        }
+    }
+
+    /// Returns the context of the current thread or a new one which is not
+    /// entered; the flag tells whether the context existed already.
+    ///
+    /// Used by futures; they can be polled by threads which run other tasks
+    /// too and must not change the context of the thread.
+    pub fn current_or_detached() -> (Arc<Self>, bool) {
+        match Self::current() {
+            Some(ctx)	=> (ctx, true),
+            None	=> (Arc::new(Self::new()), false),
+        }
+    }
+
+    pub fn push_selected(&self, reservation: u64, id: ResourceId) {
+        self.selected.lock().unwrap().push((reservation, id));
+    }
+
+    pub fn selected_by(&self, reservation: u64) -> Option<ResourceId> {
+        self.selected.lock().unwrap().iter()
+            .find(|(r, _)| *r == reservation)
+            .map(|(_, id)| id.clone())
+    }
+
+    pub fn remove_selected(&self, reservation: u64) {
+        self.selected.lock().unwrap().retain(|(r, _)| *r != reservation);
+    }
+
+    pub fn enter(ctx: Arc<Self>) -> ContextGuard {
+        let active = Arc::new(AtomicBool::new(true));
+
+        CURRENT.with(|c| c.borrow_mut().push(Entered {
+            ctx:	ctx,
+            active:	active.clone(),
+        }));
+
+        ContextGuard {
+            active:	active,
+        }
+    }
+}
+
+/// Returns the resource which has been selected for the `consumes_any`
+/// parameter of the current test.
+///
+/// For nested `#[etest]` functions, the selection of the innermost one with
+/// `consumes_any` is returned.
+///
+/// ```
+/// # use etest::etest;
+/// #[etest(consumes_any=["dev0", "dev1", "dev2"])]
+/// fn test() {
+///     let dev = etest::resources::selected().unwrap();
+///     /* ... */
+/// }
+/// ```
+pub fn selected() -> Option<ResourceId> {
+    let ctx = TestContext::current()?;
+    let selected = ctx.selected.lock().unwrap();
+
+    selected.last().map(|(_, id)| id.clone())
+}
+
+impl std::ops::Drop for ContextGuard {
+    fn drop(&mut self) {
+        self.active.store(false, Ordering::Release);
+
+        // the thread might be exiting
+        let _ = CURRENT.try_with(|c| c.borrow_mut().retain(|e| e.active.load(Ordering::Acquire)));
+    }
+}
@@ -0,0 +1,46 @@
+use std::path::Path;
+
+use super::ResourceId;
+
+/// Returns the resource which is named by the environment variable `var`
+///
+/// The variable is read at runtime of the test; this allows test hosts to
+/// assign their devices (e.g. a serial port) without changing the tests.
+/// Absolute paths are canonicalized by [`path_resource()`]; other values
+/// (e.g. `board1`) are taken as they are, even when a file with this name
+/// exists in the current directory.  Tests of different packages run in
+/// different directories and must map the same value to the same resource.
+///
+/// An unset or empty variable maps to [`ResourceId::None`](super::ResourceIdImpl::None)
+/// which is ignored.  Tests which require the resource can be skipped by
+/// checking [`ResourceId::is_some()`](super::ResourceIdImpl::is_some):
+///
+/// ```
+/// # use etest::etest;
+/// #[etest(skip=!etest::env_resource("SERIAL_PORT").is_some(),
+///         consumes=[etest::env_resource("SERIAL_PORT")])]
+/// fn test() { /* ... */ }
+/// ```
+pub fn env_resource(var: &str) -> ResourceId {
+    match std::env::var_os(var) {
+        Some(v) if Path::new(&v).is_absolute()	=> path_resource(v),
+        Some(v) if !v.is_empty()	=> ResourceId::from_string(v.to_string_lossy().into_owned()),
+        _				=> ResourceId::None,
+    }
+}
+
+/// Returns the resource for the file `path`
+///
+/// Existing files are canonicalized; e.g. `/dev/serial/by-id/usb-...` and
+/// the `/dev/ttyUSB0` which it links to map to the same resource.  Other
+/// paths are taken as they are.
+///
+/// Like all resource ids, paths are hierarchical: `/dev/ttyUSB0` implies
+/// the shared use of `/dev`.  Tests which consume `/dev` wait for all tests
+/// which hold a device resource then.
+pub fn path_resource(path: impl AsRef<Path>) -> ResourceId {
+    let path = path.as_ref();
+    let path = std::fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf());
+
+    ResourceId::from_string(path.to_string_lossy().into_owned())
+}
@@ -0,0 +1,122 @@
+use std::fs::{ File, OpenOptions };
+use std::io;
+use std::os::unix::io::AsRawFd;
+use std::path::PathBuf;
+use std::time::Duration;
+
+use once_cell::sync::Lazy;
+
+use super::ResourceId;
+
+/// Interval for checking whether resources locked by other processes became
+/// available; there is no notification for them
+pub const POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+/// Directory with the lock files; can be set by the `ETEST_LOCK_DIR`
+/// environment variable
+static LOCK_DIR: Lazy<PathBuf> = Lazy::new(|| {
+    std::env::var_os("ETEST_LOCK_DIR")
+        .map(PathBuf::from)
+        .unwrap_or_else(|| std::env::temp_dir().join("etest-locks"))
+});
+
+/// Returns the name of the lock file for resource `id`
+fn file_name(id: &ResourceId) -> Option<String> {
+    let name = match id {
+        ResourceId::Id(s)	=> s,
+        // '@' is escaped in normal ids; this avoids clashes with them
+        ResourceId::Basic	=> return Some("@basic.lock".into()),
+        ResourceId::None	=> return None,
+    };
+
+    let mut res = String::with_capacity(name.len() + 5);
+
+    for b in name.bytes() {
+        match b {
+            b'a'..=b'z' | b'A'..=b'Z' | b'0'..=b'9' | b'-' | b'_' | b'.'	=>
+                res.push(b as char),
+            b	=> res.push_str(&format!("%{b:02x}")),
+        }
+    }
+
+    res.push_str(".lock");
+
+    Some(res)
+}
+
+/// A `flock(2)` on the lock file of a resource which is held by the
+/// process as long as tests of it use or consume the resource
+///
+/// Consumed resources are locked exclusively, used ones shared.  Capacity
+/// and weights are accounted only within a process; a resource which is
+/// consumed by tests of one process can not be reserved by other processes.
+#[derive(Debug)]
+pub struct ProcessLock {
+    file:	File,
+}
+
+impl ProcessLock {
+    /// Tries to lock resource `id` without blocking.  Returns `None` when
+    /// another process holds a conflicting lock.
+    pub fn try_acquire(id: &ResourceId, exclusive: bool) -> io::Result<Option<Self>> {
+        let Some(name) = file_name(id) else {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput,
+                                      "empty resource can not be locked"));
+        };
+
+        std::fs::create_dir_all(&*LOCK_DIR)?;
+
+        let file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(LOCK_DIR.join(name))?;
+
+        let op = match exclusive {
+            true	=> libc::LOCK_EX,
+            false	=> libc::LOCK_SH,
+        };
+
+        if !try_lock(&file, op)? {
+            return Ok(None);
+        }
+
+        Ok(Some(Self {
+            file:	file,
+        }))
+    }
+
+    /// Tries to convert a shared lock into an exclusive one without
+    /// blocking.  Returns `false` when other processes hold the lock too.
+    pub fn try_upgrade(&self) -> io::Result<bool> {
+        if try_lock(&self.file, libc::LOCK_EX)? {
+            return Ok(true);
+        }
+
+        // conversion is not atomic; the shared lock has been dropped
+        // already when it failed
+        match try_lock(&self.file, libc::LOCK_SH)? {
+            true	=> Ok(false),
+            false	=> Err(io::Error::other("shared lock lost while upgrading it")),
+        }
+    }
+}
+
+/// Applies `flock(2)` operation `op` without blocking; returns `false` when
+/// another process holds a conflicting lock
+fn try_lock(file: &File, op: libc::c_int) -> io::Result<bool> {
+    // SAFETY: fd is valid for the lifetime of 'file'
+    let rc = unsafe { libc::flock(file.as_raw_fd(), op | libc::LOCK_NB) };
+
+    if rc < 0 {
+        let err = io::Error::last_os_error();
+
+        return match err.kind() {
+            io::ErrorKind::WouldBlock	=> Ok(false),
+            _			=> Err(err),
+        };
+    }
+
+    Ok(true)
+}
@@ -0,0 +1,92 @@
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::RwLock;
+use std::task::{ Context, Poll };
+
+use super::{ ResourceLockGuard, ResourceManager };
+use super::manager::PendingReservation;
+use super::notify::NotifyToken;
+use super::timer::wake_at;
+
+/// Reserves resources without blocking the thread
+///
+/// Returned by [`ResourceBuilder::lock_async()`](super::ResourceBuilder::lock_async).
+/// The future is woken when resources are released.  Dropping it before
+/// completion removes the reservation from the queue.
+pub struct ReserveFuture {
+    manager:	&'static RwLock<ResourceManager>,
+    pending:	Option<PendingReservation>,
+    /// subscription to the changes of the resources while waiting
+    token:	Option<NotifyToken>,
+    /// whether the test can be skipped when resources have been poisoned;
+    /// the future fails else
+    pub(super) can_skip:	bool,
+}
+
+impl ReserveFuture {
+    pub(super) fn new(manager: &'static RwLock<ResourceManager>, pending: PendingReservation) -> Self {
+        Self {
+            manager:	manager,
+            pending:	Some(pending),
+            token:	None,
+            can_skip:	true,
+        }
+    }
+}
+
+impl Future for ReserveFuture {
+    type Output = ResourceLockGuard;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let manager = self.manager;
+        // the first poll or a notification; else woken by a deadline
+        let mut changed = match self.token.take() {
+            Some(token)	=> token.has_changed(),
+            None	=> true,
+        };
+
+        loop {
+            let pending = self.pending.as_ref()
+                .expect("future polled after completion");
+
+            let (token, deadline) = match ResourceManager::attempt(manager, pending, changed) {
+                Ok(managed)	=> {
+                    let pending = self.pending.take().unwrap();
+
+                    self.token = None;
+
+                    let guard = ResourceManager::finish(manager, pending, managed);
+
+                    return Poll::Ready(match self.can_skip {
+                        true	=> guard,
+                        false	=> guard.fail_skipped(),
+                    });
+                },
+
+                Err(wait)	=> wait,
+            };
+
+            // resources have been changed since the attempt; retry
+            if !token.register(cx.waker()) {
+                changed = true;
+                continue;
+            }
+
+            if let Some(deadline) = deadline {
+                wake_at(cx.waker(), deadline);
+            }
+
+            self.token = Some(token);
+
+            return Poll::Pending;
+        }
+    }
+}
+
+impl std::ops::Drop for ReserveFuture {
+    fn drop(&mut self) {
+        if let Some(pending) = &self.pending {
+            ResourceManager::cancel(self.manager, pending);
+        }
+    }
+}
@@ -0,0 +1,59 @@
+use super::{ ResourceItem, ResourceRequest };
+
+/// A named list of resources
+///
+/// Groups are declared by [`resource_group!`](crate::resource_group) and can
+/// be given to the `uses` and `consumes` parameters like a single resource.
+/// Their members are evaluated when a test reserves the group.
+#[derive(Clone, Copy)]
+pub struct ResourceGroup {
+    members:	&'static [fn() -> Vec<ResourceItem>],
+}
+
+impl ResourceGroup {
+    #[doc(hidden)]
+    pub const fn new(members: &'static [fn() -> Vec<ResourceItem>]) -> Self {
+        Self {
+            members:	members,
+        }
+    }
+}
+
+impl ResourceRequest for ResourceGroup {
+    fn into_items(self) -> Vec<ResourceItem> {
+        self.members.iter()
+            .flat_map(|f| f())
+            .collect()
+    }
+}
+
+/// Declares a [`ResourceGroup`] constant
+///
+/// Members can be everything which implements [`ResourceRequest`]; this
+/// includes other groups and expressions like function calls which are
+/// evaluated at runtime of the test.
+///
+/// ```
+/// # use etest::{ etest, resource_group };
+/// # fn output() -> &'static str { "hdmi" }
+/// resource_group!(MEDIA = ["video", "audio", output()]);
+/// resource_group!(pub LAB = [MEDIA, "network"]);
+///
+/// #[etest(consumes=[MEDIA])]
+/// fn test0() { /* ... */ }
+///
+/// #[etest(uses=[LAB])]
+/// fn test1() { /* ... */ }
+/// ```
+#[macro_export]
+macro_rules! resource_group {
+    ($(#[$attr:meta])* $vis:vis $name:ident = [$($member:expr),* $(,)?]) => {
+        $(#[$attr])*
+        $vis const $name: $crate::ResourceGroup = $crate::ResourceGroup::new(&[
+            $(
+                (|| $crate::ResourceRequest::into_items($member))
+                    as fn() -> ::std::vec::Vec<$crate::ResourceItem>,
+            )*
+        ]);
+    };
+}
@@ -5,7 +5,25 @@ use std::borrow::Cow;
 /// To be used with the `uses` and `consumes` parameters of `#[etest]`.  For type
 /// safety custom types can be specified which implement `Into<ResourceId>`.
 ///
-/// E.g.
+/// The conversion can be derived by [`#[derive(ResourceId)]`](macro@crate::ResourceId);
+/// e.g.
+///
+/// ```
+/// # use etest::{ etest, ResourceId };
+/// #[derive(ResourceId)]
+/// enum Output {
+///     Hdmi,                       // "hdmi"
+///     #[resource(name = "lvds0")]
+///     Lvds,                       // "lvds0"
+///     Port(u8),                   // e.g. "port/3"
+/// }
+///
+/// #[etest(consumes=[Output::Port(3)])]
+/// fn test() {}
+/// ```
+///
+/// Conversions which depend on the runtime environment must be written by
+/// hand; e.g.
 ///
 /// ```
 /// # use etest::ResourceId;
@@ -38,6 +56,12 @@ use std::borrow::Cow;
 /// #[etest(consumes=[Output])]
 /// fn test() {}
 /// ```
+///
+/// Ids can be hierarchical by separating their components with `/`; e.g.
+/// `"usb/hub1/port3"`.  Requesting such a resource implies the shared use of
+/// its parents (`"usb"` and `"usb/hub1"`) so that a test which consumes
+/// `"usb/hub1"` does not run together with tests which use or consume
+/// `"usb/hub1/port3"`.
 #[derive(Debug, Clone, Hash, Eq, PartialEq)]
 pub enum ResourceIdImpl<'a> {
     /// Normal resource
@@ -72,6 +96,28 @@ impl <'a> ResourceIdImpl<'a> {
     pub fn is_some(&self) -> bool {
         self != &Self::None
     }
+
+    /// Returns the parents of a hierarchical id, starting with the outermost
+    /// one.
+    ///
+    /// ```
+    /// # use etest::ResourceId;
+    /// assert_eq!(ResourceId::new("usb/hub1/port3").parents(),
+    ///            [ResourceId::new("usb"), ResourceId::new("usb/hub1")]);
+    /// assert!(ResourceId::new("usb").parents().is_empty());
+    /// ```
+    pub fn parents(&self) -> Vec<ResourceId> {
+        let Self::Id(id) = self else {
+            return Vec::new();
+        };
+
+        id.match_indices('/')
+            .map(|(pos, _)| &id[..pos])
+            // ignore empty components
+            .filter(|p| !p.is_empty() && !p.ends_with('/'))
+            .map(|p| ResourceId::from_string(p.to_string()))
+            .collect()
+    }
 }
 
 impl <'a> From<&'a str> for ResourceIdImpl<'a> {
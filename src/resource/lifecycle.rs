@@ -0,0 +1,53 @@
+use std::sync::{ Mutex, PoisonError };
+
+use crate::trace_resources;
+
+/// Setup and teardown callbacks of a resource
+///
+/// `active` tells whether the resource has been set up; its lock serializes
+/// the callbacks so that tests which reserved the resource wait until the
+/// setup has been finished.
+#[derive(Debug)]
+pub struct Hooks {
+    setup:	fn(),
+    teardown:	fn(),
+    active:	Mutex<bool>,
+}
+
+impl Hooks {
+    pub fn new(setup: fn(), teardown: fn()) -> Self {
+        Self {
+            setup:	setup,
+            teardown:	teardown,
+            active:	Mutex::new(false),
+        }
+    }
+
+    /// Sets up the resource unless this has been done already.
+    ///
+    /// A failed (panicked) setup will be retried by the next test.
+    pub fn setup(&self) {
+        let mut active = self.active.lock().unwrap_or_else(PoisonError::into_inner);
+
+        if !*active {
+            trace_resources!("  setting up resource");
+            (self.setup)();
+            *active = true;
+        }
+    }
+
+    /// Tears down the resource when it has been set up and `is_unused`
+    /// tells that no test holds it anymore.
+    ///
+    /// `is_unused` is evaluated while the callbacks are locked; tests which
+    /// reserve the resource after it will set it up again.
+    pub fn teardown_if<F: FnOnce() -> bool>(&self, is_unused: F) {
+        let mut active = self.active.lock().unwrap_or_else(PoisonError::into_inner);
+
+        if *active && is_unused() {
+            trace_resources!("  tearing down resource");
+            *active = false;
+            (self.teardown)();
+        }
+    }
+}
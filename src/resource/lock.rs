@@ -1,45 +1,340 @@
-use std::sync::Arc;
+use std::future::Future;
+use std::sync::{ Arc, PoisonError, RwLock, RwLockReadGuard, RwLockWriteGuard };
 
 use crate::Location;
 use crate::trace_resources;
 
-use super::{ ResourceEntry, ResourceManagerNotify };
+use super::{ ContextGuard, ResourceEntry, ResourceId, ResourceManager, ResourceManagerNotify, TestContext };
+use super::base::release_holder;
+use super::request::PoisonPolicy;
+use super::typed::Resource;
 
+/// Holds reserved resources; they are released when the guard is dropped
+///
+/// Returned by [`ResourceBuilder::lock()`](super::ResourceBuilder::lock).
 pub struct ResourceLockGuard {
-    pub(super) managed:	Vec<ResourceEntry>,
+    /// the resources together with the id of the (possibly shared)
+    /// reservation
+    pub(super) managed:	Vec<(ResourceEntry, u64)>,
     pub(super) owner:	Location,
+    /// the manager which granted the resources
+    pub(super) manager:	&'static RwLock<ResourceManager>,
     pub(super) notify:	Arc<ResourceManagerNotify>,
+    /// test context and reservation id when a `consumes_any` candidate has
+    /// been selected
+    pub(super) selected:	Option<(Arc<TestContext>, u64)>,
+    /// indices of the resources in `managed` which have been upgraded to
+    /// ownership together with the units which have been used before
+    pub(super) upgraded:	Vec<(usize, usize)>,
+    /// the reason why the test shall be skipped; set when a resource has
+    /// been poisoned
+    pub(super) skip:	Option<String>,
+    /// time when the resources have been granted
+    #[cfg(feature = "stats")]
+    pub(super) granted:	std::time::Instant,
+    /// the test context which holds the resources
+    pub(super) context:	Arc<TestContext>,
+    // restores the test context after the resources have been released
+    pub(super) _context:	Option<ContextGuard>,
 }
 
 impl std::ops::Drop for ResourceLockGuard {
     fn drop(&mut self) {
         trace_resources!("dropping {:?}", self.owner);
+
+        if std::thread::panicking() {
+            self.poison();
+        }
+
+        // reservations which are shared with an outer one are not removed
+        self.downgrade();
         self.release();
     }
 }
 
 impl ResourceLockGuard {
     fn release(&mut self) {
-        let mut changed = false;
+        // resources which have been released by an owner or user; shared
+        // holders of nested reservations are kept
+        let mut owned = Vec::new();
+        let mut used = Vec::new();
 
-        for m in &self.managed {
+        for (m, id) in &self.managed {
             let mut entry = m.write().unwrap();
 
-            if Some(&self.owner) == entry.owner.as_ref() {
+            if release_holder(&mut entry.owners, *id) {
                 trace_resources!("  releasing owned {:?}", entry.id);
-                entry.owner = None;
-                changed = true;
+                owned.push(entry.id.clone());
             }
 
-            changed |= entry.users.remove(&self.owner);
+            if release_holder(&mut entry.users, *id) {
+                used.push(entry.id.clone());
+            }
+
+            #[cfg(feature = "flock")]
+            entry.release_process_lock();
 
             trace_resources!("  entry {:?} used by {:?}", entry.id, entry.users);
         }
 
-        self.managed.clear();
+        let managed = std::mem::take(&mut self.managed);
+
+        if let Some((ctx, id)) = self.selected.take() {
+            ctx.remove_selected(id);
+        }
+
+        if !owned.is_empty() {
+            self.notify.notify(&owned);
+        }
+
+        if !used.is_empty() {
+            self.notify.notify_capacity(&used);
+        }
+
+        #[cfg(feature = "stats")]
+        if !owned.is_empty() || !used.is_empty() {
+            super::stats::released(&self.owner, owned.into_iter().chain(used), self.granted.elapsed());
+        }
+
+        for (m, _) in managed {
+            let hooks = m.read().unwrap().hooks.clone();
+
+            if let Some(hooks) = hooks {
+                hooks.teardown_if(|| m.read().unwrap().holders().next().is_none());
+            }
+        }
+    }
+
+    /// Returns the resource which has been selected for
+    /// [`consumes_any()`](super::ResourceBuilder::consumes_any)
+    pub fn selected(&self) -> Option<ResourceId> {
+        let (ctx, id) = self.selected.as_ref()?;
+
+        ctx.selected_by(*id)
+    }
+
+    /// Runs `fut` within the test context of the reservation
+    ///
+    /// Used by `async` tests; the context is entered only while `fut` is
+    /// polled because the thread can run other tasks in between.
+    #[doc(hidden)]
+    pub fn in_context<F: Future>(&self, fut: F) -> impl Future<Output = F::Output> {
+        let ctx = self.context.clone();
+
+        async move {
+            let mut fut = std::pin::pin!(fut);
+
+            std::future::poll_fn(|cx| {
+                let _ctx = TestContext::enter(ctx.clone());
+
+                fut.as_mut().poll(cx)
+            }).await
+        }
+    }
+
+    /// Checks whether the reservation holds resource `id`; `consume`
+    /// requires ownership
+    fn holds(&self, id: &ResourceId, consume: bool) -> bool {
+        self.managed.iter().any(|(m, holder)| {
+            let entry = m.read().unwrap();
+
+            entry.id == *id &&
+                (entry.owners.iter().any(|h| h.id == *holder) ||
+                 (!consume && entry.users.iter().any(|h| h.id == *holder)))
+        })
+    }
+
+    #[track_caller]
+    fn check_access<T>(&self, res: &Resource<T>, consume: bool) {
+        let id = res.id();
+
+        if !self.holds(&id, consume) {
+            let mode = match consume {
+                true	=> "consumed",
+                false	=> "used or consumed",
+            };
+
+            panic!("resource {id:?} is not {mode} by this reservation");
+        }
+    }
+
+    /// Gives shared access to the value of `res`
+    ///
+    /// Panics when the reservation does not use or consume the resource.
+    ///
+    /// ```
+    /// # use etest::Resource;
+    /// static CONFIG: Resource<String> = Resource::new("config", String::new());
+    ///
+    /// let guard = etest::reserve().uses(&CONFIG).lock();
+    ///
+    /// println!("config={}", *guard.read(&CONFIG));
+    /// ```
+    #[track_caller]
+    pub fn read<'a, T>(&'a self, res: &'a Resource<T>) -> RwLockReadGuard<'a, T> {
+        self.check_access(res, false);
+
+        res.value.read().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Gives exclusive access to the value of `res`
+    ///
+    /// Panics when the reservation does not consume the resource (or has
+    /// not [upgraded](Self::upgrade) it).
+    #[track_caller]
+    pub fn write<'a, T>(&'a self, res: &'a Resource<T>) -> RwLockWriteGuard<'a, T> {
+        self.check_access(res, true);
+
+        res.value.write().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Upgrades the used resources of the reservation to exclusive use
+    ///
+    /// Waits until the other tests released them; tests which request them
+    /// meanwhile wait until the reservation has been downgraded or
+    /// released.  Panics on deadlocks; e.g. when two tests which use a
+    /// resource try to upgrade it.
+    ///
+    /// ```
+    /// # use etest::prelude::*;
+    /// #[etest(uses="device")]
+    /// fn test() {
+    ///     let mut device = etest::reserve().uses("device").lock();
+    ///
+    ///     // read_state();
+    ///
+    ///     device.upgrade();
+    ///     // flash();
+    ///     device.downgrade();
+    /// }
+    /// ```
+    pub fn upgrade(&mut self) {
+        let upgraded = ResourceManager::upgrade(self.manager, &self.managed, &self.owner);
 
-        if changed {
-            self.notify.notify();
+        self.upgraded.extend(upgraded);
+    }
+
+    /// Returns resources which have been upgraded by
+    /// [`upgrade()`](Self::upgrade) to shared use
+    pub fn downgrade(&mut self) {
+        if self.upgraded.is_empty() {
+            return;
+        }
+
+        let mut downgraded = Vec::new();
+
+        // NOTE: locks against other processes stay exclusive until the
+        // resources are released; converting them back is not atomic
+        for (idx, units) in std::mem::take(&mut self.upgraded) {
+            let (entry, id) = &self.managed[idx];
+            let mut e = entry.write().unwrap();
+
+            trace_resources!("  downgraded {:?}", e.id);
+            e.downgrade_holder(*id, units);
+            downgraded.push(e.id.clone());
+        }
+
+        self.notify.notify(&downgraded);
+    }
+
+    /// Poisons the consumed resources which have the `PoisonOnFailure`
+    /// policy
+    fn poison(&self) {
+        for (m, id) in &self.managed {
+            let entry = m.read().unwrap();
+
+            if let Some(poison) = &entry.poison {
+                if entry.owners.iter().any(|h| h.id == *id) {
+                    poison.poison(&self.owner);
+                }
+            }
+        }
+    }
+
+    /// Records the reason for skipping the test when a reserved resource is
+    /// not available
+    pub(super) fn check_available(&mut self) {
+        for (m, _) in &self.managed {
+            let (id, probe) = {
+                let entry = m.read().unwrap();
+
+                (entry.id.clone(), entry.probe)
+            };
+
+            if probe.is_some_and(|is_available| !is_available()) {
+                self.skip = Some(format!("{}: resource {id:?} is not available", self.owner));
+                return;
+            }
+        }
+    }
+
+    /// Checks whether reserved resources have been poisoned; fails or
+    /// records the reason for skipping the test according to their policy
+    pub(super) fn check_poisoned(&mut self) {
+        for (m, _) in &self.managed {
+            let (id, poison) = {
+                let entry = m.read().unwrap();
+
+                (entry.id.clone(), entry.poison.clone())
+            };
+
+            let Some(poison) = poison else {
+                continue;
+            };
+
+            let Some(loc) = poison.check() else {
+                continue;
+            };
+
+            let msg = format!("{}: resource {id:?} has been poisoned by {loc}", self.owner);
+
+            match poison.policy {
+                PoisonPolicy::Skip	=> {
+                    self.skip.get_or_insert(msg);
+                },
+
+                PoisonPolicy::Fail	=> {
+                    self.release();
+                    panic!("{msg}");
+                },
+            }
+        }
+    }
+
+    /// Fails when the test shall be skipped; reservations within a test can
+    /// not skip it
+    #[doc(hidden)]
+    pub fn fail_skipped(mut self) -> Self {
+        if let Some(msg) = self.skip.take() {
+            drop(self);
+            panic!("{msg}");
+        }
+
+        self
+    }
+
+    /// Tells whether the test shall be skipped because of poisoned
+    /// resources; reports the reason then
+    #[doc(hidden)]
+    pub fn is_skipped(&self, loc: &Location) -> bool {
+        let Some(msg) = &self.skip else {
+            return false;
+        };
+
+        eprintln!("{msg}");
+        crate::mark_skipped(loc);
+
+        true
+    }
+
+    /// Sets up the reserved resources which have setup callbacks
+    pub(super) fn setup(&self) {
+        for (m, _) in &self.managed {
+            let hooks = m.read().unwrap().hooks.clone();
+
+            if let Some(hooks) = hooks {
+                hooks.setup();
+            }
         }
     }
 }
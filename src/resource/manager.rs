@@ -1,16 +1,63 @@
-use std::collections::HashMap;
+use std::collections::{ BTreeMap, HashMap, HashSet };
 use std::sync::{Arc, RwLock};
+use std::sync::atomic::{ AtomicU64, Ordering };
+use std::time::Instant;
 
-use crate::{trace_resources, Location};
+use crate::{trace_resources, Location, Timeout};
 
-use super::{ Resource, ResourceId, ResourceSet, ResourceLockGuard, ResourceManagerNotify };
+use super::{ ContextGuard, Resource, ResourceId, ResourceItem, ResourceSet, ResourceLockGuard,
+             ResourceManagerNotify, TestContext };
+use super::notify::NotifyToken;
+use super::base::Holder;
+use super::lifecycle::Hooks;
+use super::poison::Poison;
+use super::rate::RateLimiter;
+use super::snapshot::{ HolderSnapshot, ResourceSnapshot, Snapshot, WaiterSnapshot };
 
 pub type ResourceEntry = Arc<RwLock<Resource>>;
 
+/// Acquired resources together with the acquired units
+type Managed = Vec<(ResourceEntry, u64)>;
+
+/// Source of the reservation ids; they define the order of arrival
+static RESERVATION_ID: AtomicU64 = AtomicU64::new(0);
+
+/// A reservation which waits for resources
+struct Waiter {
+    ctx:	u64,
+    loc:	Location,
+    request:	ResourceSet,
+    /// holders which are upgraded by the request; they do not block it
+    upgrading:	Vec<u64>,
+}
+
+/// Returns the key by which waiting reservations are served
+fn serve_order(priority: i32, id: u64) -> (std::cmp::Reverse<i32>, u64) {
+    (std::cmp::Reverse(priority), id)
+}
+
+/// Returns the time when a waiting reservation must be retried regardless
+/// of notifications; resources which are locked by other processes are
+/// released without notification
+#[cfg(feature = "flock")]
+fn retry_at(deadline: Option<Instant>) -> Option<Instant> {
+    Some(Instant::now() + super::flock::POLL_INTERVAL)
+        .into_iter()
+        .chain(deadline)
+        .min()
+}
+
+#[cfg(not(feature = "flock"))]
+fn retry_at(deadline: Option<Instant>) -> Option<Instant> {
+    deadline
+}
+
 #[derive(Default)]
 pub struct ResourceManager {
     resources:		HashMap<ResourceId, ResourceEntry>,
-    notify:		Arc<ResourceManagerNotify>,
+    /// waiting reservations, ordered by their arrival
+    waiters:		BTreeMap<u64, Waiter>,
+    pub(super) notify:	Arc<ResourceManagerNotify>,
 }
 
 impl ResourceManager {
@@ -23,93 +70,842 @@ impl ResourceManager {
         }
     }
 
-    fn try_reserve(&mut self, request: &ResourceSet, owner: &Location) -> Option<ResourceLockGuard> {
+    /// Applies the properties (e.g. capacity) declared by the request to the
+    /// managed resources and checks whether request can be fulfilled at all
+    fn declare(&mut self, request: &ResourceSet) -> Result<(), String> {
+        for item in request.items() {
+            let entry = self.find_or_insert_resource(&item.id);
+            let mut entry = entry.write().unwrap();
+
+            if let Some(capacity) = item.capacity {
+                entry.set_capacity(capacity)?;
+            }
+
+            // callbacks of the first request are kept
+            if let (Some((setup, teardown)), None) = (item.hooks, &entry.hooks) {
+                entry.hooks = Some(Hooks::new(setup, teardown).into());
+            }
+
+            if let (Some((policy, reset)), None) = (item.poison, &entry.poison) {
+                entry.poison = Some(Poison::new(policy, reset).into());
+            }
+
+            entry.probe = entry.probe.or(item.probe);
+
+            match item.rate {
+                Some((0, _))	=> return Err(format!("rate limit of {:?} does not allow any start", item.id)),
+                Some((starts, per)) if entry.rate.is_none()	=>
+                    entry.rate = Some(RateLimiter::new(starts, per)),
+                _	=> {},
+            }
+        }
+
+        for (id, units) in request.consumed().chain(request.used()).chain(request.candidates()) {
+            self.find_or_insert_resource(id)
+                .read().unwrap()
+                .check_units(units)?;
+        }
+
+        Ok(())
+    }
+
+    /// Removes waiting reservation `id` from the queue; reservations which
+    /// have been queued behind it might proceed now
+    fn remove_waiter(&mut self, id: u64) {
+        let Some(w) = self.waiters.remove(&id) else {
+            return;
+        };
+
+        let consumed = w.request.consumed()
+            .chain(w.request.candidates())
+            .map(|(id, _)| id);
+
+        self.notify.notify(consumed);
+
+        // reservations which use a resource without weight did not queue
+        // behind other users
+        self.notify.notify_capacity(w.request.used()
+                                    .map(|(id, _)| id)
+                                    .chain(w.request.any_parents.values().flatten()));
+    }
+
+    /// Checks whether a reservation which is served before `request`
+    /// competes for the requested resource.
+    ///
+    /// Reservations are served by their priority and then in the order of
+    /// their arrival.
+    fn is_queued(&self, request: &ResourceSet, holder: &Holder,
+                 id: &ResourceId, consume: bool, units: usize) -> bool {
+        let this_order = serve_order(request.priority, holder.id);
+
+        self.waiters.iter()
+            .filter(|(w_id, w)| serve_order(w.request.priority, **w_id) < this_order)
+            .any(|(_, w)| w.ctx != holder.ctx && w.request.competes_with(id, consume, units))
+    }
+
+    /// Checks whether `units` of resource `id` can be granted to `holder`
+    /// now; see [`Self::try_reserve()`] for the ordering of reservations.
+    fn is_available(&mut self, request: &ResourceSet, holder: &Holder, nested: bool,
+                    id: &ResourceId, consume: bool, units: usize) -> bool {
+        let entry = self.find_or_insert_resource(id);
+        let entry = entry.read().unwrap();
+
+        if entry.covering_holder(holder.ctx, consume, units).is_some() {
+            return true;
+        }
+
+        if !entry.can_reserve(consume, units) {
+            trace_resources!("  entry {:?} already owned by {:?} or used by {:?}",
+                             entry.id, entry.owners, entry.users);
+            return false;
+        }
+
+        if !nested && self.is_queued(request, holder, id, consume, units) {
+            trace_resources!("  entry {:?} requested by an earlier test", entry.id);
+            return false;
+        }
+
+        if let Some(at) = entry.rate.as_ref().and_then(|r| r.next_token(Instant::now())) {
+            trace_resources!("  entry {:?} exceeds its rate limit until {:?}", entry.id, at);
+            return false;
+        }
+
+        true
+    }
+
+    /// Returns the time when a rate limited resource of `request` which is
+    /// not held by test context `ctx` can be granted again
+    fn rate_limited_until(&self, request: &ResourceSet, ctx: u64) -> Option<Instant> {
+        let now = Instant::now();
+
+        request.requested()
+            .chain(request.candidates().map(|(id, units)| (id, true, units)))
+            .filter_map(|(id, consume, units)| {
+                let entry = self.resources.get(id)?.read().unwrap();
+
+                match entry.covering_holder(ctx, consume, units) {
+                    Some(_)	=> None,
+                    None	=> entry.rate.as_ref()?.next_token(now),
+                }
+            })
+            .min()
+    }
+
+    /// Tries to acquire the requested resources.
+    ///
+    /// Unless `nested` is set, resources are granted by the priority and the
+    /// order of the reservations; e.g. a test which uses a resource will wait
+    /// behind an earlier test which waits to consume it.  Nested reservations
+    /// are made by tests which hold resources already; letting them wait in
+    /// the queue could cause deadlocks.
+    ///
+    /// Of the `consumes_any` candidates, one which is held by the test
+    /// already is preferred; else the first available one is taken.  It is
+    /// acquired as the last resource.
+    ///
+    /// Returns an error when resources can not be locked against other
+    /// processes.
+    fn try_reserve(&mut self, request: &ResourceSet, holder: &Holder, nested: bool)
+                   -> Result<Option<Managed>, String> {
         let mut managed = Vec::new();
 
-        trace_resources!("trying to acquire resources for {}", owner);
+        trace_resources!("trying to acquire resources for {}", holder.loc);
 
         // first step: check whether requested resources are available.
         //
         // because they can be reserved only by going through the ResourceManager,
         // they are available when reserving them later
-        for req in &request.consumes {
-            let entry = self.find_or_insert_resource(req);
-            let entry = entry.read().unwrap();
+        for (req, consume, units) in request.requested() {
+            if !self.is_available(request, holder, nested, req, consume, units) {
+                return Ok(None);
+            }
+        }
 
-            if entry.owner.is_some() || !entry.users.is_empty() {
-                trace_resources!("  entry {:?} already owned by {:?} or used by {:?}",
-                                 entry.id, entry.owner, entry.users);
-                return None;
+        let fixed: Vec<_> = request.requested().collect();
+
+        let requested = match request.consumes_any.is_empty() {
+            true if self.lock_process(&fixed)?	=> fixed,
+            true	=> return Ok(None),
+
+            false	=> {
+                let mut candidates: Vec<_> = request.candidates()
+                    .filter(|(req, units)| request.with_parents(req, *units)
+                            .all(|(id, consume, units)| self.is_available(request, holder, nested,
+                                                                          id, consume, units)))
+                    .collect();
+
+                // stable sort keeps the order of the other candidates
+                candidates.sort_by_key(|(req, units)| self.resources[*req]
+                                       .read().unwrap()
+                                       .covering_holder(holder.ctx, true, *units)
+                                       .is_none());
+
+                let mut selected = None;
+
+                for (req, units) in candidates {
+                    let mut all = fixed.clone();
+
+                    all.extend(request.with_parents(req, units));
+
+                    if self.lock_process(&all)? {
+                        selected = Some(all);
+                        break;
+                    }
+                }
+
+                match selected {
+                    Some(all)	=> all,
+                    None	=> {
+                        trace_resources!("  no candidate available");
+                        return Ok(None);
+                    }
+                }
+            }
+        };
+
+        // second step: acquire the resources
+        for (req, consume, units) in requested {
+            let entry = self.resources.get(req).unwrap();
+            let mut e = entry.write().unwrap();
+
+            // nested reservations of the same test share the existing ones
+            if let Some(id) = e.covering_holder(holder.ctx, consume, units) {
+                trace_resources!("  sharing {:?}", e.id);
+                e.share_holder(id);
+                managed.push((entry.clone(), id));
+                continue;
+            }
+
+            assert!(e.can_reserve(consume, units));
+
+            if let Some(rate) = &mut e.rate {
+                rate.take(Instant::now());
+            }
+
+            let new_holder = Holder { units: units, ..holder.clone() };
+
+            match consume {
+                true	=> {
+                    trace_resources!("  acquired {:?} for ownership", e.id);
+                    e.owners.push(new_holder);
+                },
+                false	=> {
+                    trace_resources!("  acquired {:?}", e.id);
+                    e.users.push(new_holder);
+                },
+            }
+
+            managed.push((entry.clone(), holder.id));
+        }
+
+        Ok(Some(managed))
+    }
+
+    /// Returns the requested resources which are not available for
+    /// `holder`; when no candidate of `consumes_any` is available, all of
+    /// them are returned.
+    #[cfg(feature = "stats")]
+    fn blockers(&mut self, request: &ResourceSet, holder: &Holder, nested: bool) -> Vec<ResourceId> {
+        let mut blockers: Vec<_> = request.requested()
+            .filter(|(id, consume, units)| !self.is_available(request, holder, nested, id, *consume, *units))
+            .map(|(id, _, _)| id.clone())
+            .collect();
+
+        let candidates: Vec<_> = request.candidates()
+            .filter(|(id, units)| !request.with_parents(id, *units)
+                    .all(|(id, consume, units)| self.is_available(request, holder, nested, id, consume, units)))
+            .map(|(id, _)| id.clone())
+            .collect();
+
+        if candidates.len() == request.consumes_any.len() {
+            blockers.extend(candidates);
+        }
+
+        blockers
+    }
+
+    /// Locks the requested resources which are not held yet by this process
+    /// against other processes.
+    ///
+    /// Returns `false` when one of them is locked by another process; locks
+    /// which have been acquired by this call are released then.
+    #[cfg(feature = "flock")]
+    fn lock_process(&self, requested: &[(&ResourceId, bool, usize)]) -> Result<bool, String> {
+        use super::flock::ProcessLock;
+
+        let mut locked = Vec::new();
+        let mut res = Ok(true);
+
+        for &(req, consume, _) in requested {
+            let entry = &self.resources[req];
+            let mut e = entry.write().unwrap();
+
+            // the resource is held already by tests of this process; it is
+            // available because the request passed the checks above
+            if e.process_lock.is_some() {
+                continue;
+            }
+
+            match ProcessLock::try_acquire(req, consume) {
+                Ok(Some(lock))	=> {
+                    e.process_lock = Some(lock);
+                    locked.push(entry.clone());
+                },
+
+                Ok(None)	=> {
+                    trace_resources!("  entry {:?} locked by another process", e.id);
+                    res = Ok(false);
+                    break;
+                },
+
+                Err(err)	=> {
+                    res = Err(format!("failed to lock resource {req:?}: {err}"));
+                    break;
+                },
             }
         }
 
-        for req in &request.uses {
-            let entry = self.find_or_insert_resource(req);
-            let entry = entry.read().unwrap();
+        if !matches!(res, Ok(true)) {
+            for entry in locked {
+                entry.write().unwrap().process_lock = None;
+            }
+        }
+
+        res
+    }
+
+    #[cfg(not(feature = "flock"))]
+    fn lock_process(&self, _requested: &[(&ResourceId, bool, usize)]) -> Result<bool, String> {
+        Ok(true)
+    }
+
+    /// Checks whether `units` of resource `id` could be granted to test
+    /// context `ctx` when only the holders matched by `counts` are
+    /// considered
+    fn is_grantable<F>(&self, id: &ResourceId, consume: bool, units: usize, ctx: u64, counts: F) -> bool
+    where
+        F: Fn(&Holder) -> bool,
+    {
+        // resources which are not known yet are not held by anybody
+        let Some(entry) = self.resources.get(id) else {
+            return true;
+        };
+
+        let entry = entry.read().unwrap();
+
+        entry.covering_holder(ctx, consume, units).is_some() ||
+            entry.can_reserve_if(consume, units, counts)
+    }
+
+    /// Checks whether `request` of test context `ctx` could be granted when
+    /// all holders which are not in `stuck` contexts released their
+    /// resources.  The `upgrading` holders are not considered.
+    fn is_satisfiable(&self, request: &ResourceSet, ctx: u64, upgrading: &[u64],
+                      stuck: &HashSet<u64>) -> bool {
+        let counts = |h: &Holder| stuck.contains(&h.ctx) && !upgrading.contains(&h.id);
+
+        request.requested()
+            .all(|(id, consume, units)| self.is_grantable(id, consume, units, ctx, counts)) &&
+            (request.consumes_any.is_empty() ||
+             request.candidates().any(|(id, units)| request.with_parents(id, units)
+                                      .all(|(id, consume, units)| self.is_grantable(id, consume, units,
+                                                                                    ctx, counts))))
+    }
+
+    /// Checks whether the waiting reservation `id` can ever be granted.
+    ///
+    /// Contexts which are not waiting will release their resources
+    /// eventually; so will waiters which can be satisfied by these resources.
+    /// Waiters which remain are part of (or blocked by) a cycle.
+    ///
+    /// Returns a description of the blocking resources when a deadlock has
+    /// been detected.
+    fn find_deadlock(&self, id: u64) -> Option<String> {
+        let mut stuck: HashSet<u64> = self.waiters.values().map(|w| w.ctx).collect();
+
+        loop {
+            let live = self.waiters.values()
+                .filter(|w| stuck.contains(&w.ctx))
+                .find(|w| self.is_satisfiable(&w.request, w.ctx, &w.upgrading, &stuck));
+
+            match live {
+                Some(w)	=> stuck.remove(&w.ctx),
+                None	=> break,
+            };
+        }
+
+        let waiter = &self.waiters[&id];
 
-            if entry.owner.is_some() {
-                trace_resources!("  entry {:?} already owned by {:?}", entry.id, entry.owner);
+        if !stuck.contains(&waiter.ctx) {
+            return None;
+        }
+
+        Some(self.describe_blocked(&waiter.request, waiter.ctx,
+                                   |h| stuck.contains(&h.ctx) && !waiter.upgrading.contains(&h.id)))
+    }
+
+    /// Returns a description of the resources which block `request` of test
+    /// context `ctx` together with their holders.  Only holders matched by
+    /// `counts` are considered.
+    fn describe_blocked<F>(&self, request: &ResourceSet, ctx: u64, counts: F) -> String
+    where
+        F: Fn(&Holder) -> bool,
+    {
+        let fmt_holders = |holders: &[Holder]| holders.iter()
+            .filter(|h| counts(h))
+            .map(|h| match h.ctx == ctx {
+                true	=> format!("{} (same test)", h.loc),
+                false	=> h.loc.to_string(),
+            })
+            .collect::<Vec<_>>()
+            .join(", ");
+
+        let describe = |id: &ResourceId, consume: bool, units: usize| {
+            if self.is_grantable(id, consume, units, ctx, &counts) {
                 return None;
             }
+
+            let entry = self.resources[id].read().unwrap();
+
+            Some(format!("{:?} owned by [{}] and used by [{}]", entry.id,
+                         fmt_holders(&entry.owners), fmt_holders(&entry.users)))
+        };
+
+        let mut blocked: Vec<_> = request.requested()
+            .filter_map(|(id, consume, units)| describe(id, consume, units))
+            .collect();
+
+        // a candidate is blocked by itself or by one of its parents
+        let candidates: Vec<_> = request.candidates()
+            .map(|(id, units)| {
+                let blocked: Vec<_> = request.with_parents(id, units)
+                    .filter_map(|(id, consume, units)| describe(id, consume, units))
+                    .collect();
+
+                match blocked.is_empty() {
+                    true	=> None,
+                    false	=> Some(blocked.join(", ")),
+                }
+            })
+            .collect();
+
+        // all candidates are blocked
+        if !candidates.is_empty() && candidates.iter().all(Option::is_some) {
+            blocked.push(format!("any of {}", candidates.into_iter()
+                                 .flatten()
+                                 .collect::<Vec<_>>()
+                                 .join(", ")));
         }
 
-        // second step: acquire the resources
-        for req in &request.consumes {
-            let entry = self.resources.get(req).unwrap();
-            managed.push(entry.clone());
+        if blocked.is_empty() && self.rate_limited_until(request, ctx).is_some() {
+            return "resources exceed their rate limit".into();
+        }
 
-            let mut entry = entry.write().unwrap();
+        // the resources are available within this process but are locked
+        // by other ones
+        if blocked.is_empty() && cfg!(feature = "flock") {
+            return "resources are locked by other processes".into();
+        }
+
+        blocked.join("; ")
+    }
+
+    /// Checks whether test context `ctx` holds resource `id`; `consume`
+    /// requires ownership
+    pub fn is_held(&self, id: &ResourceId, ctx: u64, consume: bool) -> bool {
+        self.resources.get(id)
+            .is_some_and(|e| e.read().unwrap().covering_holder(ctx, consume, 0).is_some())
+    }
 
-            assert!(entry.owner.is_none());
+    pub fn snapshot(&self) -> Snapshot {
+        let holders = |holders: &[Holder]| holders.iter()
+            .map(|h| HolderSnapshot {
+                test:	h.loc.clone(),
+                units:	h.units,
+            })
+            .collect();
 
-            trace_resources!("  acquired {:?} for ownership", entry.id);
-            entry.owner = Some(owner.clone());
+        let resources = self.resources.values()
+            .map(|e| {
+                let e = e.read().unwrap();
+
+                ResourceSnapshot {
+                    id:		e.id.clone(),
+                    capacity:	e.capacity(),
+                    owners:	holders(&e.owners),
+                    users:	holders(&e.users),
+                }
+            })
+            .collect();
+
+        let mut waiters: Vec<_> = self.waiters.iter().collect();
+
+        waiters.sort_by_key(|(id, w)| serve_order(w.request.priority, **id));
+
+        let waiters = waiters.into_iter()
+            .map(|(_, w)| WaiterSnapshot {
+                test:		w.loc.clone(),
+                priority:	w.request.priority,
+                consumes:	w.request.consumes.keys().cloned().collect(),
+                uses:		w.request.uses.keys().cloned().collect(),
+                consumes_any:	w.request.candidates().map(|(id, _)| id.clone()).collect(),
+            })
+            .collect();
+
+        Snapshot {
+            resources:	resources,
+            waiters:	waiters,
         }
+    }
 
-        for req in &request.uses {
-            let entry = self.resources.get(req).unwrap();
-            managed.push(entry.clone());
+    /// Prepares the reservation of `request`; panics when it can never be
+    /// fulfilled.
+    ///
+    /// Reservations of futures (`is_async`) get a new context unless the
+    /// thread runs a test already; it is not entered because the thread can
+    /// run other tasks while the future waits or holds the resources.
+    pub(super) fn start(this: &RwLock<Self>, request: ResourceSet, owner: &Location,
+                        timeout: Option<Timeout>, is_async: bool) -> PendingReservation {
+        let (ctx, ctx_guard, nested) = match is_async {
+            true	=> {
+                let (ctx, exists) = TestContext::current_or_detached();
 
-            let mut entry = entry.write().unwrap();
+                (ctx, None, exists)
+            },
+
+            false	=> {
+                let (ctx, ctx_guard) = TestContext::current_or_new();
+                // a context exists already when test holds resources
+                let nested = ctx_guard.is_none();
+
+                (ctx, ctx_guard, nested)
+            },
+        };
 
-            assert!(entry.owner.is_none());
+        let holder = Holder {
+            id:		RESERVATION_ID.fetch_add(1, Ordering::Relaxed),
+            ctx:	ctx.id,
+            loc:	owner.clone(),
+            units:	0,
+            refs:	1,
+        };
+
+        // NOTE: do not panic while holding the lock; it would poison it for
+        // all other tests
+        let declared = this.write().unwrap().declare(&request);
+
+        if let Err(e) = declared {
+            panic!("{owner}: {e}");
+        }
 
-            trace_resources!("  acquired {:?}", entry.id);
-            entry.users.insert(owner.clone());
+        PendingReservation {
+            request:	request,
+            holder:	holder,
+            ctx:	ctx,
+            ctx_guard:	ctx_guard,
+            nested:	nested,
+            deadline:	timeout.map(|t| Instant::now() + t.duration()),
         }
+    }
+
+    /// Tries to acquire the resources of `pending`.
+    ///
+    /// When they are not available, the reservation is queued and a token
+    /// for waiting on the next change is returned together with the time
+    /// when the next attempt must be made regardless of notifications.
+    /// Panics on deadlocks and when the timeout expired.
+    ///
+    /// `changed` tells whether this is the first attempt or whether the
+    /// resources have been changed since the last one; deadlocks can not
+    /// arise else and are not searched.  Retries which are caused by polling
+    /// do not pay for it then.
+    pub(super) fn attempt(this: &RwLock<Self>, pending: &PendingReservation, changed: bool)
+                          -> Result<Managed, (NotifyToken, Option<Instant>)> {
+        let PendingReservation { request, holder, ctx, nested, deadline, .. } = pending;
+        let owner = &holder.loc;
+
+        let mut mgr = this.write().unwrap();
+        let token = mgr.notify.token(request.interests());
+
+        let failure = match mgr.try_reserve(request, holder, *nested) {
+            Err(e)		=> {
+                mgr.remove_waiter(holder.id);
+                Some(e)
+            },
+
+            Ok(Some(managed))	=> {
+                trace_resources!("resources aquired for {owner}");
+                mgr.remove_waiter(holder.id);
+
+                #[cfg(feature = "stats")]
+                super::stats::granted(holder.id, managed.iter().map(|(e, _)| e.read().unwrap().id.clone()));
+
+                return Ok(managed);
+            },
+
+            Ok(None)	=> {
+                mgr.waiters.entry(holder.id).or_insert_with(|| Waiter {
+                    ctx:		ctx.id,
+                    loc:		owner.clone(),
+                    request:	request.clone(),
+                    upgrading:	Vec::new(),
+                });
+
+                #[cfg(feature = "stats")]
+                {
+                    let blockers = mgr.blockers(request, holder, *nested);
+
+                    super::stats::blocked(holder.id, owner, blockers);
+                }
+
+                let is_expired = deadline.is_some_and(|d| Instant::now() >= d);
+
+                let deadlock = match changed {
+                    true	=> mgr.find_deadlock(holder.id),
+                    false	=> None,
+                };
+
+                let failure = match deadlock {
+                    Some(blocked)		=> Some(format!("DEADLOCK; {blocked}")),
+                    None if is_expired	=> Some(format!(
+                        "TIMEOUT while waiting for resources; {}",
+                        mgr.describe_blocked(request, ctx.id, |_| true))),
+                    None			=> None,
+                };
+
+                if failure.is_some() {
+                    mgr.remove_waiter(holder.id);
+                }
+
+                failure
+            }
+        };
+
+        // the tokens of rate limited resources are returned without
+        // notification
+        let retry = retry_at(*deadline).into_iter()
+            .chain(mgr.rate_limited_until(request, ctx.id))
+            .min();
+
+        drop(mgr);
+
+        if let Some(msg) = failure {
+            #[cfg(feature = "stats")]
+            super::stats::cancelled(holder.id);
 
-        Some(ResourceLockGuard {
+            panic!("{owner}: {msg}");
+        }
+
+        trace_resources!("resource not available yet for {owner}; waiting...");
+
+        Err((token, retry))
+    }
+
+    /// Removes a reservation which will not be continued (e.g. because an
+    /// async test has been cancelled) from the queue
+    pub(super) fn cancel(this: &RwLock<Self>, pending: &PendingReservation) {
+        let mut mgr = this.write().unwrap();
+
+        #[cfg(feature = "stats")]
+        super::stats::cancelled(pending.holder.id);
+
+        mgr.remove_waiter(pending.holder.id);
+    }
+
+    /// Creates the guard for the acquired resources and sets them up
+    pub(super) fn finish(this: &'static RwLock<Self>, pending: PendingReservation,
+                         managed: Managed) -> ResourceLockGuard {
+        let PendingReservation { request, holder, ctx, ctx_guard, .. } = pending;
+
+        // the selected candidate has been acquired last
+        let selected = match request.consumes_any.is_empty() {
+            true	=> None,
+            false	=> {
+                let id = managed.last().unwrap().0.read().unwrap().id.clone();
+
+                ctx.push_selected(holder.id, id);
+                Some((ctx.clone(), holder.id))
+            }
+        };
+
+        let mut guard = ResourceLockGuard {
             managed:	managed,
-            owner:	owner.clone(),
-            notify:	self.notify.clone(),
-        })
+            owner:	holder.loc,
+            manager:	this,
+            notify:	this.read().unwrap().notify.clone(),
+            selected:	selected,
+            upgraded:	Vec::new(),
+            skip:	None,
+            #[cfg(feature = "stats")]
+            granted:	Instant::now(),
+            context:	ctx,
+            _context:	ctx_guard,
+        };
+
+        // runs outside of the manager lock; a panic releases the resources.
+        // Unavailable resources are neither reset nor set up.
+        guard.check_available();
+
+        if guard.skip.is_none() {
+            guard.check_poisoned();
+        }
+
+        if guard.skip.is_none() {
+            guard.setup();
+        }
+
+        guard
     }
 
-    pub fn reserve(this: &RwLock<Self>, request: ResourceSet, owner: &Location) -> ResourceLockGuard {
+    /// Tries to turn the holders of `upgrades` from users into owners; the
+    /// items contain the resource, the id of the holder and the units which
+    /// it will own.  Returns the units which they used before.
+    ///
+    /// Returns an error when resources can not be locked exclusively
+    /// against other processes.
+    fn try_upgrade(upgrades: &[(ResourceEntry, u64, usize)]) -> Result<Option<Vec<usize>>, String> {
+        let available = upgrades.iter()
+            .all(|(e, id, units)| e.read().unwrap().can_consume_if(*units, |h| h.id != *id));
+
+        if !available {
+            trace_resources!("  resources still used by other tests");
+            return Ok(None);
+        }
+
+        #[cfg(feature = "flock")]
+        for (e, _, _) in upgrades {
+            let e = e.read().unwrap();
+
+            match e.process_lock.as_ref().map(|l| l.try_upgrade()) {
+                None | Some(Ok(true))	=> {},
+                Some(Ok(false))	=> {
+                    trace_resources!("  entry {:?} used by another process", e.id);
+                    return Ok(None);
+                },
+                Some(Err(err))	=>
+                    return Err(format!("failed to lock resource {:?} exclusively: {err}", e.id)),
+            }
+        }
+
+        let prev = upgrades.iter()
+            .map(|(e, id, units)| {
+                let mut e = e.write().unwrap();
+
+                trace_resources!("  upgraded {:?}", e.id);
+                e.upgrade_holder(*id, *units)
+            })
+            .collect();
+
+        Ok(Some(prev))
+    }
+
+    /// Upgrades the reservations of `managed` which use their resource to
+    /// ownership; waits until the other holders released the resources.
+    /// Reservations which arrive meanwhile are served after the upgrade.
+    ///
+    /// Returns the indices of the upgraded resources together with the
+    /// units which have been used before.  Panics on deadlocks.
+    pub(super) fn upgrade(this: &RwLock<Self>, managed: &Managed, owner: &Location) -> Vec<(usize, usize)> {
+        let mut indices = Vec::new();
+        let mut upgrades = Vec::new();
+        let mut request = ResourceSet::default();
+        let mut ctx = None;
+
+        for (idx, (entry, id)) in managed.iter().enumerate() {
+            let e = entry.read().unwrap();
+
+            let Some(holder) = e.users.iter().find(|h| h.id == *id) else {
+                continue;
+            };
+
+            // exclusive use requires at least one unit
+            let units = holder.units.max(1);
+
+            ctx = Some(holder.ctx);
+            indices.push(idx);
+            upgrades.push((entry.clone(), *id, units));
+            request.consumes.insert(e.id.clone(), ResourceItem {
+                units:	Some(units),
+                ..ResourceItem::new(e.id.clone())
+            });
+        }
+
+        let Some(ctx) = ctx else {
+            return Vec::new();
+        };
+
+        let waiter_id = RESERVATION_ID.fetch_add(1, Ordering::Relaxed);
+        // see attempt() for when deadlocks are searched
+        let mut changed = true;
+
         loop {
-            // NOTE: do not write this as the match scrutinee; it will hold
-            // the lock during wait() else
             let mut mgr = this.write().unwrap();
-            let token = mgr.notify.token();
-            let resource = mgr.try_reserve(&request, owner);
+            let token = mgr.notify.token(request.interests());
+
+            let failure = match Self::try_upgrade(&upgrades) {
+                Err(e)		=> Some(e),
+
+                Ok(Some(prev))	=> {
+                    mgr.remove_waiter(waiter_id);
+                    return indices.into_iter().zip(prev).collect();
+                },
+
+                Ok(None)	=> {
+                    mgr.waiters.entry(waiter_id).or_insert_with(|| Waiter {
+                        ctx:		ctx,
+                        loc:		owner.clone(),
+                        request:	request.clone(),
+                        upgrading:	upgrades.iter().map(|(_, id, _)| *id).collect(),
+                    });
+
+                    match changed {
+                        true	=> mgr.find_deadlock(waiter_id)
+                            .map(|blocked| format!("DEADLOCK while upgrading resources; {blocked}")),
+                        false	=> None,
+                    }
+                },
+            };
+
+            if failure.is_some() {
+                mgr.remove_waiter(waiter_id);
+            }
 
             drop(mgr);
 
-            match resource {
-                Some(g)		=> {
-                    trace_resources!("resources aquired for {owner}");
-                    break g;
-                }
-                None		=> {
-                    trace_resources!("resource not available yet for {owner}; waiting...");
-                    let notify = this.read().unwrap().notify.clone();
-
-                    // do not combine this with above; it will hold the lock
-                    // on 'this' else which might block at the beginning of
-                    // another loop
-                    notify.wait(token);
-                }
+            if let Some(msg) = failure {
+                panic!("{owner}: {msg}");
             }
+
+            token.wait(retry_at(None));
+            changed = token.has_changed();
         }
     }
+
+    pub fn reserve(this: &'static RwLock<Self>, request: ResourceSet, owner: &Location,
+                   timeout: Option<Timeout>) -> ResourceLockGuard {
+        let pending = Self::start(this, request, owner, timeout, false);
+        let mut changed = true;
+
+        loop {
+            let (token, wake_at) = match Self::attempt(this, &pending, changed) {
+                Ok(managed)	=> return Self::finish(this, pending, managed),
+                Err(wait)	=> wait,
+            };
+
+            // NOTE: the lock on 'this' is not held while waiting; it would
+            // block the next attempt of all other tests
+            token.wait(wake_at);
+            changed = token.has_changed();
+        }
+    }
+}
+
+/// A reservation which waits for its resources
+pub struct PendingReservation {
+    request:	ResourceSet,
+    holder:	Holder,
+    ctx:	Arc<TestContext>,
+    ctx_guard:	Option<ContextGuard>,
+    nested:	bool,
+    deadline:	Option<Instant>,
 }
@@ -7,18 +7,41 @@ mod builder;
 mod manager;
 mod notify;
 mod lock;
+mod request;
+mod context;
+mod future;
+mod group;
+mod lifecycle;
+mod env;
+mod poison;
+mod rate;
+mod timer;
+pub(crate) mod snapshot;
+pub(crate) mod typed;
+#[cfg(feature = "flock")]
+mod flock;
+#[cfg(feature = "stats")]
+mod stats;
 
-pub use builder::ResourceBuilder;
+pub use builder::{ reserve, ResourceBuilder };
+pub use env::{ env_resource, path_resource };
 pub use id::ResourceId;
 
 pub use id::ResourceIdImpl;
+pub use request::{ Lifecycle, PoisonOnFailure, PoisonPolicy, Probe, RateLimit, ResourceRequest, ResourceItem,
+                   Slots, Weight };
+pub use group::ResourceGroup;
+pub(crate) use context::TestContext;
+pub use context::selected;
 
 use base::Resource;
 use set::ResourceSet;
 use manager::ResourceManager;
 use manager::ResourceEntry;
 use notify::ResourceManagerNotify;
-use lock::ResourceLockGuard;
+pub use lock::ResourceLockGuard;
+pub use future::ReserveFuture;
+use context::ContextGuard;
 
 /// Internal global object which manages the resouces.
 pub static RESOURCES: Lazy<std::sync::RwLock<ResourceManager>> = Lazy::new(Default::default);
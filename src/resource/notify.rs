@@ -1,33 +1,186 @@
-use std::sync::{ Condvar, Mutex };
+use std::collections::HashMap;
+use std::sync::{ Arc, Condvar, Mutex };
+use std::task::Waker;
+use std::time::Instant;
 
-pub struct NotifyToken(u64);
+use super::ResourceId;
 
 #[derive(Default)]
-pub struct ResourceManagerNotify {
+struct State {
+    /// set when one of the resources has been changed
+    changed:	bool,
+    /// waker of an async reservation
+    waker:	Option<Waker>,
+}
+
+/// A waiting reservation which is interested in changes of some resources
+#[derive(Default)]
+struct Subscriber {
     notify:	Condvar,
-    lock:	Mutex<u64>,
+    lock:	Mutex<State>,
 }
 
-impl ResourceManagerNotify {
-    pub fn notify(&self) {
+impl Subscriber {
+    fn wake(&self) {
         let mut l = self.lock.lock().unwrap();
 
-        *l += 1;
-
+        l.changed = true;
         self.notify.notify_all();
+
+        let waker = l.waker.take();
+
+        drop(l);
+
+        if let Some(w) = waker {
+            w.wake();
+        }
+    }
+}
+
+/// Subscribers of a resource together with a flag whether they use the
+/// resource without weight; such waiters are not blocked by other users
+type Subscribers = Vec<(Arc<Subscriber>, bool)>;
+
+/// Receives the notifications about changes of the resources which have
+/// been given to [`ResourceManagerNotify::token()`]; it is unsubscribed when
+/// dropped
+pub struct NotifyToken {
+    subscriber:	Arc<Subscriber>,
+    resources:	Vec<ResourceId>,
+    notify:	Arc<ResourceManagerNotify>,
+}
+
+/// Wakes reservations which wait for resources when they have been changed
+///
+/// Waiters subscribe to the resources which they need; releasing a resource
+/// wakes only the waiters of this resource.
+#[derive(Default)]
+pub struct ResourceManagerNotify {
+    subscribers:	Mutex<HashMap<ResourceId, Subscribers>>,
+}
+
+impl ResourceManagerNotify {
+    /// Wakes the waiters of `resources`
+    pub fn notify<'a>(&self, resources: impl IntoIterator<Item = &'a ResourceId>) {
+        self.wake(resources, true);
+    }
+
+    /// Wakes the waiters of `resources` which need capacity of them; i.e.
+    /// which consume them or use them with weight.  Others are not blocked
+    /// by users; e.g. releasing a user does not affect them.
+    pub fn notify_capacity<'a>(&self, resources: impl IntoIterator<Item = &'a ResourceId>) {
+        self.wake(resources, false);
     }
 
-    pub fn token(&self) -> NotifyToken {
-        let serial = self.lock.lock().unwrap();
+    /// Wakes the waiters of `resources`; unless `all` is set, only the ones
+    /// which need ownership or units of them
+    fn wake<'a>(&self, resources: impl IntoIterator<Item = &'a ResourceId>, all: bool) {
+        let subscribers = self.subscribers.lock().unwrap();
 
-        NotifyToken(*serial)
+        let woken: Vec<_> = resources.into_iter()
+            .filter_map(|id| subscribers.get(id))
+            .flatten()
+            .filter(|(_, shared)| all || !shared)
+            .map(|(s, _)| s.clone())
+            .collect();
+
+        drop(subscribers);
+
+        for s in woken {
+            s.wake();
+        }
     }
 
-    pub fn wait(&self, token: NotifyToken) {
-        let mut serial = self.lock.lock().unwrap();
+    /// Subscribes to changes of `resources`; they are given together with
+    /// a flag whether they are used without weight.
+    ///
+    /// The token must be created before checking the resources; changes
+    /// which happen afterwards are not missed then.
+    pub fn token<'a>(self: &Arc<Self>, resources: impl IntoIterator<Item = (&'a ResourceId, bool)>)
+                     -> NotifyToken {
+        let subscriber = Arc::new(Subscriber::default());
+        let mut subscribers = self.subscribers.lock().unwrap();
+
+        let resources = resources.into_iter()
+            .map(|(id, shared)| {
+                subscribers.entry(id.clone())
+                    .or_default()
+                    .push((subscriber.clone(), shared));
+
+                id.clone()
+            })
+            .collect();
+
+        NotifyToken {
+            subscriber:	subscriber,
+            resources:	resources,
+            notify:	self.clone(),
+        }
+    }
+}
+
+impl NotifyToken {
+    /// Waits until one of the resources has been changed after the token
+    /// has been created or `deadline` has been reached.
+    pub fn wait(&self, deadline: Option<Instant>) {
+        let s = &self.subscriber;
+        let mut l = s.lock.lock().unwrap();
+
+        while !l.changed {
+            l = match deadline {
+                None	=> s.notify.wait(l).unwrap(),
+                Some(d)	=> {
+                    let now = Instant::now();
+
+                    if now >= d {
+                        break;
+                    }
+
+                    s.notify.wait_timeout(l, d - now).unwrap().0
+                }
+            };
+        }
+    }
+
+    /// Tells whether one of the resources has been changed after the token
+    /// has been created
+    pub fn has_changed(&self) -> bool {
+        self.subscriber.lock.lock().unwrap().changed
+    }
+
+    /// Registers `waker` for being woken by the next change.
+    ///
+    /// Returns `false` when a resource has been changed after the token has
+    /// been created already; the waker is not registered then.
+    pub fn register(&self, waker: &Waker) -> bool {
+        let mut l = self.subscriber.lock.lock().unwrap();
+
+        if l.changed {
+            return false;
+        }
+
+        if !l.waker.as_ref().is_some_and(|w| w.will_wake(waker)) {
+            l.waker = Some(waker.clone());
+        }
+
+        true
+    }
+}
+
+impl std::ops::Drop for NotifyToken {
+    fn drop(&mut self) {
+        let mut subscribers = self.notify.subscribers.lock().unwrap();
+
+        for id in &self.resources {
+            let Some(list) = subscribers.get_mut(id) else {
+                continue;
+            };
+
+            list.retain(|(s, _)| !Arc::ptr_eq(s, &self.subscriber));
 
-        while *serial == token.0 {
-            serial = self.notify.wait(serial).unwrap();
+            if list.is_empty() {
+                subscribers.remove(id);
+            }
         }
     }
 }
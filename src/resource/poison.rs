@@ -0,0 +1,51 @@
+use std::sync::{ Mutex, PoisonError };
+
+use crate::Location;
+use crate::trace_resources;
+
+use super::request::PoisonPolicy;
+
+/// Poisoning state of a resource with the
+/// [`PoisonOnFailure`](super::PoisonOnFailure) policy
+///
+/// `poisoned` holds the test which failed while consuming the resource; its
+/// lock serializes the reset callbacks.
+#[derive(Debug)]
+pub struct Poison {
+    pub(super) policy:	PoisonPolicy,
+    reset:	Option<fn() -> bool>,
+    poisoned:	Mutex<Option<Location>>,
+}
+
+impl Poison {
+    pub fn new(policy: PoisonPolicy, reset: Option<fn() -> bool>) -> Self {
+        Self {
+            policy:	policy,
+            reset:	reset,
+            poisoned:	Mutex::new(None),
+        }
+    }
+
+    /// Marks the resource as poisoned by test `loc`
+    pub fn poison(&self, loc: &Location) {
+        let mut poisoned = self.poisoned.lock().unwrap_or_else(PoisonError::into_inner);
+
+        trace_resources!("  poisoned by {loc}");
+        poisoned.get_or_insert_with(|| loc.clone());
+    }
+
+    /// Returns the test which poisoned the resource
+    ///
+    /// Tries to reset a poisoned resource first; a successful reset clears
+    /// the poisoning.
+    pub fn check(&self) -> Option<Location> {
+        let mut poisoned = self.poisoned.lock().unwrap_or_else(PoisonError::into_inner);
+
+        if poisoned.is_some() && self.reset.is_some_and(|reset| reset()) {
+            trace_resources!("  reset poisoned resource");
+            *poisoned = None;
+        }
+
+        poisoned.clone()
+    }
+}
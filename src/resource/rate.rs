@@ -0,0 +1,50 @@
+use std::collections::VecDeque;
+use std::time::{ Duration, Instant };
+
+/// Token bucket of a resource with a [`RateLimit`](super::RateLimit)
+///
+/// The bucket holds `starts` tokens; every reservation which starts to hold
+/// the resource takes one and it is returned `per` after it has been taken.
+/// Hence, no more than `starts` reservations are granted within any time
+/// window of length `per`.
+#[derive(Debug)]
+pub struct RateLimiter {
+    starts:	usize,
+    per:	Duration,
+    /// the times when the tokens have been taken; the oldest one first
+    taken:	VecDeque<Instant>,
+}
+
+impl RateLimiter {
+    pub fn new(starts: usize, per: Duration) -> Self {
+        Self {
+            starts:	starts,
+            per:	per,
+            taken:	VecDeque::with_capacity(starts),
+        }
+    }
+
+    /// Returns the time when the next token will be available or `None`
+    /// when one is available at `now`
+    pub fn next_token(&self, now: Instant) -> Option<Instant> {
+        let active = self.taken.iter()
+            .filter(|t| now < **t + self.per)
+            .count();
+
+        match active < self.starts {
+            true	=> None,
+            // tokens are returned in the order they have been taken
+            false	=> Some(self.taken[self.taken.len() - active] + self.per),
+        }
+    }
+
+    /// Takes a token at `now`; one must be available
+    pub fn take(&mut self, now: Instant) {
+        while self.taken.front().is_some_and(|t| now >= *t + self.per) {
+            self.taken.pop_front();
+        }
+
+        debug_assert!(self.taken.len() < self.starts);
+        self.taken.push_back(now);
+    }
+}
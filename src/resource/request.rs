@@ -0,0 +1,329 @@
+use std::time::Duration;
+
+use super::ResourceId;
+
+/// Something which can be given to the `uses` and `consumes` parameters
+///
+/// This is implemented for everything which implements
+/// [`Into<ResourceId>`](ResourceId) and for wrappers like [`Slots`] which
+/// attach additional properties to a resource.
+pub trait ResourceRequest {
+    #[doc(hidden)]
+    fn into_items(self) -> Vec<ResourceItem>;
+}
+
+/// The setup and teardown callbacks of a [`Lifecycle`]
+pub(super) type Callbacks = (fn(), fn());
+
+/// The policy and reset callback of [`PoisonOnFailure`]
+pub(super) type PoisonSpec = (PoisonPolicy, Option<fn() -> bool>);
+
+/// A single, resolved resource request
+#[doc(hidden)]
+#[derive(Debug, Clone)]
+pub struct ResourceItem {
+    pub(super) id:		ResourceId,
+    pub(super) capacity:	Option<usize>,
+    pub(super) units:	Option<usize>,
+    /// setup and teardown callbacks
+    pub(super) hooks:	Option<Callbacks>,
+    pub(super) poison:	Option<PoisonSpec>,
+    /// availability probe
+    pub(super) probe:	Option<fn() -> bool>,
+    /// number of starts per time window
+    pub(super) rate:	Option<(usize, Duration)>,
+}
+
+impl ResourceItem {
+    pub fn new(id: ResourceId) -> Self {
+        Self {
+            id:		id,
+            capacity:	None,
+            units:	None,
+            hooks:	None,
+            poison:	None,
+            probe:	None,
+            rate:	None,
+        }
+    }
+
+    /// Merges the properties of `other` into `self`; both items must refer
+    /// to the same resource.
+    pub(super) fn merge(&mut self, other: Self) {
+        debug_assert_eq!(self.id, other.id);
+
+        if other.capacity.is_some() {
+            self.capacity = other.capacity;
+        }
+
+        self.units = self.units.max(other.units);
+        self.hooks = self.hooks.or(other.hooks);
+        self.poison = self.poison.or(other.poison);
+        self.probe = self.probe.or(other.probe);
+        self.rate = self.rate.or(other.rate);
+    }
+}
+
+impl <T: Into<ResourceId>> ResourceRequest for T {
+    fn into_items(self) -> Vec<ResourceItem> {
+        vec![ResourceItem::new(self.into())]
+    }
+}
+
+/// A resource with a capacity
+///
+/// Up to the given number of tests can consume such a resource at the same
+/// time.  E.g. when there are three identical serial adapters attached,
+///
+/// ```
+/// # use etest::{ etest, Slots };
+/// #[etest(consumes=[Slots("serial", 3)])]
+/// fn test() { /* ... */ }
+/// ```
+///
+/// allows to run three such tests in parallel.
+///
+/// "Using" a counted resource is still exclusive to "consuming" it; e.g. a
+/// test with `uses="serial"` runs only when no test occupies a slot.  See
+/// [`Weight`] for requesting more than one slot.
+///
+/// All requests for a resource must declare the same capacity; a resource
+/// without declared capacity has a single slot.
+#[derive(Debug, Clone, Copy)]
+pub struct Slots<T>(pub T, pub usize);
+
+impl <T: Into<ResourceId>> ResourceRequest for Slots<T> {
+    fn into_items(self) -> Vec<ResourceItem> {
+        let mut item = ResourceItem::new(self.0.into());
+
+        item.capacity = Some(self.1);
+
+        vec![item]
+    }
+}
+
+/// Requests a given number of units of a resource
+///
+/// Tests which "consume" a resource occupy one slot of it by default; tests
+/// which "use" it none.  `Weight` overrides this number and the test will
+/// run only when the sum of the units requested by all consumers (or
+/// respectively by all users) fits into the capacity of the resource.
+///
+/// ```
+/// # use etest::{ etest, Slots, Weight };
+/// const MEMORY: Slots<&str> = Slots("memory", 16);
+///
+/// #[etest(uses=[Weight(MEMORY, 4)])]
+/// fn test_small() { /* ... */ }
+///
+/// #[etest(uses=[Weight(MEMORY, 12)])]
+/// fn test_big() { /* ... */ }
+/// ```
+///
+/// When the same resource is requested multiple times by a test, the
+/// largest weight is taken.  Requesting more units than the capacity of the
+/// resource causes the test to panic.
+#[derive(Debug, Clone, Copy)]
+pub struct Weight<T>(pub T, pub usize);
+
+impl <T: ResourceRequest> ResourceRequest for Weight<T> {
+    fn into_items(self) -> Vec<ResourceItem> {
+        let mut items = self.0.into_items();
+
+        for item in &mut items {
+            item.units = Some(self.1);
+        }
+
+        items
+    }
+}
+
+/// A resource with setup and teardown callbacks
+///
+/// `setup` is called when a test reserves the resource while no other test
+/// holds it; `teardown` when the last test which holds it finishes.  Tests
+/// wait until the setup has been finished.
+///
+/// ```
+/// # use etest::{ etest, Lifecycle };
+/// fn start_database() { /* ... */ }
+/// fn stop_database() { /* ... */ }
+///
+/// const DATABASE: Lifecycle<&str> = Lifecycle {
+///     resource: "database",
+///     setup:    start_database,
+///     teardown: stop_database,
+/// };
+///
+/// #[etest(uses=[DATABASE])]
+/// fn test() { /* ... */ }
+/// ```
+///
+/// The callbacks are registered by the first test which requests the
+/// resource; later requests can use the plain resource id.  When `setup`
+/// panics, the test fails and the next test which reserves the resource
+/// will retry it.
+#[derive(Debug, Clone, Copy)]
+pub struct Lifecycle<T> {
+    pub resource:	T,
+    pub setup:		fn(),
+    pub teardown:	fn(),
+}
+
+impl <T: ResourceRequest> ResourceRequest for Lifecycle<T> {
+    fn into_items(self) -> Vec<ResourceItem> {
+        let mut items = self.resource.into_items();
+
+        for item in &mut items {
+            item.hooks = Some((self.setup, self.teardown));
+        }
+
+        items
+    }
+}
+
+/// Tells what happens to tests which request a poisoned resource
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PoisonPolicy {
+    /// the test fails
+    Fail,
+    /// the test is skipped.  Reservations by [`reserve()`](crate::reserve)
+    /// within a test fail; so do tests which return a value unless they
+    /// declare `skip` or `skip_result`.
+    Skip,
+}
+
+/// A resource which is poisoned when a test fails while consuming it
+///
+/// Tests which request the resource afterwards fail or are skipped
+/// (according to `policy`) with a message which names the test that
+/// poisoned it.  When a `reset` callback is given, it is called by these
+/// tests first; when it returns `true`, the poisoning is cleared and the
+/// test runs.
+///
+/// ```
+/// # use etest::{ etest, PoisonOnFailure, PoisonPolicy };
+/// fn power_cycle() -> bool {
+///     /* ... */
+/// # true
+/// }
+///
+/// const DEVICE: PoisonOnFailure<&str> = PoisonOnFailure {
+///     resource: "device",
+///     policy:   PoisonPolicy::Skip,
+///     reset:    Some(power_cycle),
+/// };
+///
+/// #[etest(consumes=[DEVICE])]
+/// fn test() { /* ... */ }
+/// ```
+///
+/// Like with [`Lifecycle`], the policy is registered by the first test
+/// which requests the resource.  Only tests which consume the resource can
+/// poison it.
+#[derive(Debug, Clone, Copy)]
+pub struct PoisonOnFailure<T> {
+    pub resource:	T,
+    pub policy:		PoisonPolicy,
+    pub reset:		Option<fn() -> bool>,
+}
+
+impl <T: ResourceRequest> ResourceRequest for PoisonOnFailure<T> {
+    fn into_items(self) -> Vec<ResourceItem> {
+        let mut items = self.resource.into_items();
+
+        for item in &mut items {
+            item.poison = Some((self.policy, self.reset));
+        }
+
+        items
+    }
+}
+
+/// A resource which is not available on every test host
+///
+/// Tests which use or consume the resource are skipped when `is_available`
+/// returns `false`; e.g.
+///
+/// ```
+/// # use etest::{ etest, Probe };
+/// fn has_video() -> bool {
+///     std::path::Path::new("/dev/video0").exists()
+/// }
+///
+/// const VIDEO: Probe<&str> = Probe {
+///     resource:     "video",
+///     is_available: has_video,
+/// };
+///
+/// #[etest(consumes=[VIDEO])]
+/// fn test() { /* ... */ }
+/// ```
+///
+/// The probe is called after the resource has been reserved, for every
+/// reservation.  Like with [`Lifecycle`], it is registered by the first
+/// test which requests the resource.  Skipping has the same restrictions as
+/// with [`PoisonPolicy::Skip`]; e.g. reservations within a test fail.
+#[derive(Debug, Clone, Copy)]
+pub struct Probe<T> {
+    pub resource:	T,
+    pub is_available:	fn() -> bool,
+}
+
+impl <T: ResourceRequest> ResourceRequest for Probe<T> {
+    fn into_items(self) -> Vec<ResourceItem> {
+        let mut items = self.resource.into_items();
+
+        for item in &mut items {
+            item.probe = Some(self.is_available);
+        }
+
+        items
+    }
+}
+
+/// A resource which limits how often tests can start to hold it
+///
+/// Unlike [`Slots`] which limits the number of tests holding a resource at
+/// the same time, `RateLimit` limits the number of reservations which are
+/// granted within a time window; e.g. a license server which rejects bursts
+/// of requests can be protected by
+///
+/// ```
+/// # use std::time::Duration;
+/// # use etest::{ etest, RateLimit };
+/// const LICENSE_SERVER: RateLimit<&str> = RateLimit {
+///     resource: "license-server",
+///     starts:   5,
+///     per:      Duration::from_secs(1),
+/// };
+///
+/// #[etest(uses=[LICENSE_SERVER])]
+/// fn test() { /* ... */ }
+/// ```
+///
+/// which grants the resource to at most 5 tests per second.  Further tests
+/// wait until the reservation would not exceed the rate anymore; they are
+/// served in the usual order.  Reservations of a test which holds the
+/// resource already are not limited.
+///
+/// Like with [`Lifecycle`], the limit is registered by the first test which
+/// requests the resource.
+#[derive(Debug, Clone, Copy)]
+pub struct RateLimit<T> {
+    pub resource:	T,
+    pub starts:		usize,
+    pub per:		Duration,
+}
+
+impl <T: ResourceRequest> ResourceRequest for RateLimit<T> {
+    fn into_items(self) -> Vec<ResourceItem> {
+        let mut items = self.resource.into_items();
+
+        for item in &mut items {
+            item.rate = Some((self.starts, self.per));
+        }
+
+        items
+    }
+}
@@ -1,8 +1,81 @@
-use std::collections::HashSet;
+use std::collections::HashMap;
 
-use super::ResourceId;
+use super::{ ResourceId, ResourceItem };
 
+#[derive(Clone, Default)]
 pub struct ResourceSet {
-    pub(super) uses:		HashSet<ResourceId>,
-    pub(super) consumes:	HashSet<ResourceId>,
+    pub(super) uses:		HashMap<ResourceId, ResourceItem>,
+    pub(super) consumes:	HashMap<ResourceId, ResourceItem>,
+    /// candidates of which one will be consumed
+    pub(super) consumes_any:	Vec<ResourceItem>,
+    /// parents of the candidates which are not requested otherwise; they
+    /// are used together with the selected candidate
+    pub(super) any_parents:	HashMap<ResourceId, Vec<ResourceId>>,
+    pub(super) priority:	i32,
+}
+
+impl ResourceSet {
+    pub(super) fn items(&self) -> impl Iterator<Item = &ResourceItem> {
+        self.consumes.values()
+            .chain(self.uses.values())
+            .chain(self.consumes_any.iter())
+    }
+
+    /// Returns the consumed resources with the requested number of units
+    pub(super) fn consumed(&self) -> impl Iterator<Item = (&ResourceId, usize)> {
+        self.consumes.iter().map(|(id, item)| (id, item.units.unwrap_or(1)))
+    }
+
+    /// Returns the used resources with the requested number of units
+    pub(super) fn used(&self) -> impl Iterator<Item = (&ResourceId, usize)> {
+        self.uses.iter().map(|(id, item)| (id, item.units.unwrap_or(0)))
+    }
+
+    /// Returns the candidates of `consumes_any` with the requested number
+    /// of units
+    pub(super) fn candidates(&self) -> impl Iterator<Item = (&ResourceId, usize)> {
+        self.consumes_any.iter().map(|item| (&item.id, item.units.unwrap_or(1)))
+    }
+
+    /// Returns the resources which are reserved when candidate `id` is
+    /// selected; i.e. the shared use of its parents and the candidate
+    /// itself which comes last
+    pub(super) fn with_parents<'a>(&'a self, id: &'a ResourceId, units: usize)
+                                   -> impl Iterator<Item = (&'a ResourceId, bool, usize)> {
+        self.any_parents.get(id).into_iter().flatten()
+            .map(|p| (p, false, 0))
+            .chain(std::iter::once((id, true, units)))
+    }
+
+    /// Returns all requested resources except the candidates, whether they are consumed and the
+    /// requested number of units
+    pub(super) fn requested(&self) -> impl Iterator<Item = (&ResourceId, bool, usize)> {
+        self.consumed().map(|(id, units)| (id, true, units))
+            .chain(self.used().map(|(id, units)| (id, false, units)))
+    }
+
+    /// Returns the requested resources including the candidates together
+    /// with a flag whether they are used without weight; the reservation
+    /// is not blocked by other users of them then
+    pub(super) fn interests(&self) -> impl Iterator<Item = (&ResourceId, bool)> {
+        self.requested()
+            .map(|(id, consume, units)| (id, !consume && units == 0))
+            .chain(self.candidates().map(|(id, _)| (id, false)))
+            .chain(self.any_parents.values().flatten().map(|id| (id, true)))
+    }
+
+    /// Checks whether this set and a request of `units` of resource `id`
+    /// would be competing for it
+    pub(super) fn competes_with(&self, id: &ResourceId, consume: bool, units: usize) -> bool {
+        if self.consumes.contains_key(id) || self.consumes_any.iter().any(|item| &item.id == id) {
+            return true;
+        }
+
+        match self.uses.get(id) {
+            None	=> consume && self.any_parents.values().flatten().any(|p| p == id),
+            Some(_) if consume	=> true,
+            // weighted usage competes for the capacity
+            Some(item)	=> units > 0 && item.units.unwrap_or(0) > 0,
+        }
+    }
 }
@@ -0,0 +1,157 @@
+use std::fmt;
+
+use crate::Location;
+
+use super::{ ResourceId, RESOURCES };
+
+/// A test which holds units of a resource
+#[derive(Debug, Clone)]
+pub struct HolderSnapshot {
+    pub test:		Location,
+    pub units:		usize,
+}
+
+/// State of a single resource
+#[derive(Debug, Clone)]
+pub struct ResourceSnapshot {
+    pub id:		ResourceId,
+    pub capacity:	usize,
+    /// tests which consume the resource
+    pub owners:		Vec<HolderSnapshot>,
+    /// tests which use the resource
+    pub users:		Vec<HolderSnapshot>,
+}
+
+/// A test which waits for resources
+#[derive(Debug, Clone)]
+pub struct WaiterSnapshot {
+    pub test:		Location,
+    pub priority:	i32,
+    pub consumes:	Vec<ResourceId>,
+    pub uses:		Vec<ResourceId>,
+    /// candidates of `consumes_any` in the order of preference
+    pub consumes_any:	Vec<ResourceId>,
+}
+
+/// State of all resources which are known by the process
+///
+/// Waiting tests are listed in the order in which they will be served.
+#[derive(Debug, Clone)]
+pub struct Snapshot {
+    pub resources:	Vec<ResourceSnapshot>,
+    pub waiters:	Vec<WaiterSnapshot>,
+}
+
+/// Returns the current state of all resources
+///
+/// ```
+/// # use etest::etest;
+/// #[etest(consumes="video")]
+/// fn test() {
+///     println!("{}", etest::resources::snapshot());
+/// }
+/// ```
+pub fn snapshot() -> Snapshot {
+    let mut snapshot = RESOURCES.read().unwrap().snapshot();
+
+    snapshot.resources.sort_by_cached_key(|r| fmt_id(&r.id));
+
+    for w in &mut snapshot.waiters {
+        w.consumes.sort_by_cached_key(fmt_id);
+        w.uses.sort_by_cached_key(fmt_id);
+    }
+
+    snapshot
+}
+
+/// Formats `id` as shown in reports
+pub(super) fn fmt_id(id: &ResourceId) -> String {
+    match id {
+        ResourceId::Id(id)	=> id.to_string(),
+        ResourceId::Basic	=> "<default>".into(),
+        ResourceId::None	=> "<none>".into(),
+    }
+}
+
+fn fmt_ids(ids: &[ResourceId]) -> String {
+    ids.iter()
+        .map(fmt_id)
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+fn fmt_holders(holders: &[HolderSnapshot]) -> String {
+    holders.iter()
+        .map(|h| match h.units {
+            0 | 1	=> h.test.to_string(),
+            u	=> format!("{} [{u}]", h.test),
+        })
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// Writes `rows` as a table with aligned columns
+fn fmt_table(f: &mut fmt::Formatter<'_>, rows: &[Vec<String>]) -> fmt::Result {
+    let mut widths = Vec::new();
+
+    for row in rows {
+        widths.resize(widths.len().max(row.len()), 0);
+
+        for (w, cell) in widths.iter_mut().zip(row) {
+            *w = (*w).max(cell.chars().count());
+        }
+    }
+
+    for row in rows {
+        let mut line = String::new();
+
+        for (w, cell) in widths.iter().zip(row) {
+            line.push_str(&format!("{cell:w$}  "));
+        }
+
+        writeln!(f, "{}", line.trim_end())?;
+    }
+
+    Ok(())
+}
+
+impl fmt::Display for Snapshot {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut rows = vec![
+            vec!["RESOURCE".into(), "CAPACITY".into(), "OWNERS".into(), "USERS".into()],
+        ];
+
+        for r in &self.resources {
+            rows.push(vec![
+                fmt_id(&r.id),
+                r.capacity.to_string(),
+                fmt_holders(&r.owners),
+                fmt_holders(&r.users),
+            ]);
+        }
+
+        fmt_table(f, &rows)?;
+
+        if self.waiters.is_empty() {
+            return Ok(());
+        }
+
+        let mut rows = vec![
+            vec!["WAITING".into(), "PRIORITY".into(), "CONSUMES".into(), "USES".into(),
+                 "CONSUMES ANY".into()],
+        ];
+
+        for w in &self.waiters {
+            rows.push(vec![
+                w.test.to_string(),
+                w.priority.to_string(),
+                fmt_ids(&w.consumes),
+                fmt_ids(&w.uses),
+                fmt_ids(&w.consumes_any),
+            ]);
+        }
+
+        writeln!(f)?;
+        fmt_table(f, &rows)
+    }
+}
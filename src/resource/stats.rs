@@ -0,0 +1,195 @@
+use std::collections::{ HashMap, HashSet };
+use std::io::Write;
+use std::sync::{ Mutex, PoisonError, TryLockError };
+use std::time::{ Duration, Instant };
+
+use once_cell::sync::Lazy;
+
+use crate::Location;
+
+use super::ResourceId;
+use super::snapshot::fmt_id;
+
+#[derive(Default)]
+struct ResourceStats {
+    /// number of granted reservations
+    reservations:	usize,
+    /// number of reservations which had to wait for the resource
+    contended:	usize,
+    waited:	Duration,
+    held:	Duration,
+}
+
+#[derive(Default)]
+struct TestStats {
+    waited:	Duration,
+    held:	Duration,
+}
+
+/// A reservation which waits for resources
+struct Waiting {
+    test:	String,
+    /// start of the current wait interval
+    since:	Instant,
+    /// resources which blocked the reservation at the start of the interval
+    blockers:	Vec<ResourceId>,
+    /// resources which blocked the reservation at some time
+    seen:	HashSet<ResourceId>,
+}
+
+#[derive(Default)]
+struct Stats {
+    resources:	HashMap<ResourceId, ResourceStats>,
+    tests:	HashMap<String, TestStats>,
+    waiting:	HashMap<u64, Waiting>,
+}
+
+static STATS: Lazy<Mutex<Stats>> = Lazy::new(|| {
+    extern "C" fn report_at_exit() {
+        report();
+    }
+
+    // SAFETY: registers a plain function which does not unwind
+    unsafe { libc::atexit(report_at_exit) };
+
+    Default::default()
+});
+
+fn stats() -> std::sync::MutexGuard<'static, Stats> {
+    // statistics stay usable when a test panicked while recording them
+    STATS.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+impl Stats {
+    /// Attributes the time since the last attempt of reservation `id` to the
+    /// resources which blocked it then
+    fn close_interval(&mut self, id: u64) {
+        let Some(waiting) = self.waiting.get_mut(&id) else {
+            return;
+        };
+
+        let now = Instant::now();
+        let duration = now - waiting.since;
+
+        for r in &waiting.blockers {
+            self.resources.entry(r.clone()).or_default().waited += duration;
+        }
+
+        self.tests.entry(waiting.test.clone()).or_default().waited += duration;
+        waiting.since = now;
+    }
+}
+
+/// Records that reservation `id` of `test` has to wait for `blockers`
+pub fn blocked(id: u64, test: &Location, blockers: Vec<ResourceId>) {
+    let mut stats = stats();
+
+    stats.close_interval(id);
+
+    let waiting = stats.waiting.entry(id).or_insert_with(|| Waiting {
+        test:	test.to_string(),
+        since:	Instant::now(),
+        blockers:	Vec::new(),
+        seen:	HashSet::new(),
+    });
+
+    let new: Vec<_> = blockers.iter()
+        .filter(|r| waiting.seen.insert((*r).clone()))
+        .cloned()
+        .collect();
+
+    waiting.blockers = blockers;
+
+    for r in new {
+        stats.resources.entry(r).or_default().contended += 1;
+    }
+}
+
+/// Records that reservation `id` has been granted `resources`
+pub fn granted(id: u64, resources: impl IntoIterator<Item = ResourceId>) {
+    let mut stats = stats();
+
+    stats.close_interval(id);
+    stats.waiting.remove(&id);
+
+    for r in resources {
+        stats.resources.entry(r).or_default().reservations += 1;
+    }
+}
+
+/// Records that reservation `id` will not wait anymore without being granted
+pub fn cancelled(id: u64) {
+    let mut stats = stats();
+
+    stats.close_interval(id);
+    stats.waiting.remove(&id);
+}
+
+/// Records that `test` released `resources` which it held for `held`
+pub fn released(test: &Location, resources: impl IntoIterator<Item = ResourceId>, held: Duration) {
+    let mut stats = stats();
+
+    for r in resources {
+        stats.resources.entry(r).or_default().held += held;
+    }
+
+    stats.tests.entry(test.to_string()).or_default().held += held;
+}
+
+fn fmt_duration(d: Duration) -> String {
+    format!("{:.3}s", d.as_secs_f64())
+}
+
+fn write_report(w: &mut dyn Write, stats: &Stats) -> std::io::Result<()> {
+    let program = std::env::args().next().unwrap_or_default();
+
+    let mut resources: Vec<_> = stats.resources.iter().collect();
+    let mut tests: Vec<_> = stats.tests.iter().collect();
+
+    resources.sort_by_cached_key(|(id, r)| (std::cmp::Reverse(r.waited), fmt_id(id)));
+    tests.sort_by_key(|(name, t)| (std::cmp::Reverse(t.waited), *name));
+
+    writeln!(w, "resource statistics of {program}")?;
+    writeln!(w, "{:>12} {:>10} {:>12} {:>12}  resource", "reservations", "contended", "waited", "held")?;
+
+    for (id, r) in resources {
+        writeln!(w, "{:>12} {:>10} {:>12} {:>12}  {}", r.reservations, r.contended,
+                 fmt_duration(r.waited), fmt_duration(r.held), fmt_id(id))?;
+    }
+
+    writeln!(w)?;
+    writeln!(w, "{:>12} {:>12}  test", "waited", "held")?;
+
+    for (name, t) in tests {
+        writeln!(w, "{:>12} {:>12}  {name}", fmt_duration(t.waited), fmt_duration(t.held))?;
+    }
+
+    writeln!(w)
+}
+
+/// Writes the statistics to the file given by the `ETEST_STATS_FILE`
+/// environment variable or to stderr.  Reports of several processes are
+/// appended to the file.
+fn report() {
+    // a test which did not finish might still record statistics while the
+    // process exits; waiting for it would hang the exit
+    let stats = match STATS.try_lock() {
+        Ok(stats)	=> stats,
+        Err(TryLockError::Poisoned(e))	=> e.into_inner(),
+        Err(TryLockError::WouldBlock)	=> return,
+    };
+
+    if stats.resources.is_empty() {
+        return;
+    }
+
+    // errors can not be reported anymore when the process exits
+    let _ = match std::env::var_os("ETEST_STATS_FILE") {
+        Some(path)	=> std::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .and_then(|mut f| write_report(&mut f, &stats)),
+        None		=> write_report(&mut std::io::stderr(), &stats),
+    };
+}
@@ -0,0 +1,87 @@
+//! Wakes futures at deadlines without an async runtime
+//!
+//! A single thread serves all deadlines; it is started by the first one.
+
+use std::sync::{ Condvar, Mutex, PoisonError };
+use std::task::Waker;
+use std::time::Instant;
+
+use once_cell::sync::Lazy;
+
+struct Timer {
+    /// the pending deadlines; one for every waker
+    deadlines:	Mutex<Vec<(Instant, Waker)>>,
+    changed:	Condvar,
+}
+
+static TIMER: Lazy<&'static Timer> = Lazy::new(|| {
+    let timer: &'static Timer = Box::leak(Box::new(Timer {
+        deadlines:	Mutex::new(Vec::new()),
+        changed:	Condvar::new(),
+    }));
+
+    std::thread::Builder::new()
+        .name("etest-timer".into())
+        .spawn(move || timer.run())
+        .expect("failed to start timer thread");
+
+    timer
+});
+
+impl Timer {
+    fn run(&self) -> ! {
+        let mut deadlines = self.deadlines.lock().unwrap_or_else(PoisonError::into_inner);
+
+        loop {
+            let now = Instant::now();
+            let mut expired = Vec::new();
+
+            deadlines.retain(|(at, waker)| match *at <= now {
+                true	=> {
+                    expired.push(waker.clone());
+                    false
+                },
+                false	=> true,
+            });
+
+            // wakers might call back into the timer
+            if !expired.is_empty() {
+                drop(deadlines);
+
+                for w in expired {
+                    w.wake();
+                }
+
+                deadlines = self.deadlines.lock().unwrap_or_else(PoisonError::into_inner);
+                continue;
+            }
+
+            deadlines = match deadlines.iter().map(|(at, _)| *at).min() {
+                None		=> self.changed.wait(deadlines)
+                    .unwrap_or_else(PoisonError::into_inner),
+                Some(at)	=> self.changed
+                    .wait_timeout(deadlines, at.saturating_duration_since(now))
+                    .unwrap_or_else(PoisonError::into_inner)
+                    .0,
+            };
+        }
+    }
+}
+
+/// Wakes `waker` at `deadline`
+///
+/// A waker is woken only once; when it has been registered already, the
+/// earlier deadline is kept.  The future will register the later one again
+/// when it is polled then.
+pub fn wake_at(waker: &Waker, deadline: Instant) {
+    let timer = *TIMER;
+    let mut deadlines = timer.deadlines.lock().unwrap_or_else(PoisonError::into_inner);
+
+    match deadlines.iter_mut().find(|(_, w)| w.will_wake(waker)) {
+        Some((at, _)) if *at <= deadline	=> return,
+        Some((at, _))	=> *at = deadline,
+        None		=> deadlines.push((deadline, waker.clone())),
+    }
+
+    timer.changed.notify_one();
+}
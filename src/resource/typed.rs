@@ -0,0 +1,97 @@
+use std::sync::{ PoisonError, RwLock, RwLockReadGuard, RwLockWriteGuard };
+
+use super::{ ResourceId, TestContext, RESOURCES };
+
+/// A resource which guards a value
+///
+/// The value can be accessed only by tests which reserved the resource:
+/// [`read()`](Self::read) requires that the test uses or consumes it,
+/// [`write()`](Self::write) that it consumes it.
+///
+/// ```
+/// # use etest::{ etest, Resource };
+/// static COUNTER: Resource<u32> = Resource::new("counter", 0);
+///
+/// #[etest(consumes=[&COUNTER])]
+/// fn test0() {
+///     *COUNTER.write() += 1;
+/// }
+///
+/// #[etest(uses=[&COUNTER])]
+/// fn test1() {
+///     println!("counter={}", *COUNTER.read());
+/// }
+/// ```
+///
+/// Access is checked against the reservations of the current test; threads
+/// which are spawned by the test do not have access.  Returned guards should
+/// not be held while calling `#[etest]` functions which access the value
+/// too.
+///
+/// Test functions can not take arguments; hence the value is not passed to
+/// them but accessed through the static.  Reservations within a test can
+/// access it through their guard by
+/// [`ResourceLockGuard::read()`](super::ResourceLockGuard::read) and
+/// [`ResourceLockGuard::write()`](super::ResourceLockGuard::write); these
+/// are checked against the reservation of the guard and can not outlive it.
+pub struct Resource<T> {
+    id:		&'static str,
+    pub(super) value:	RwLock<T>,
+}
+
+impl <T> Resource<T> {
+    pub const fn new(id: &'static str, value: T) -> Self {
+        Self {
+            id:		id,
+            value:	RwLock::new(value),
+        }
+    }
+
+    pub fn id(&self) -> ResourceId {
+        ResourceId::new(self.id)
+    }
+
+    #[track_caller]
+    fn check_access(&self, consume: bool) {
+        let id = self.id();
+        let is_held = match TestContext::current() {
+            None	=> false,
+            Some(ctx)	=> RESOURCES.read().unwrap().is_held(&id, ctx.id, consume),
+        };
+
+        if !is_held {
+            let mode = match consume {
+                true	=> "consumed",
+                false	=> "used or consumed",
+            };
+
+            panic!("resource {id:?} is not {mode} by the current test");
+        }
+    }
+
+    /// Gives shared access to the value
+    ///
+    /// Panics when the current test does not use or consume the resource.
+    #[track_caller]
+    pub fn read(&self) -> RwLockReadGuard<'_, T> {
+        self.check_access(false);
+
+        self.value.read().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Gives exclusive access to the value
+    ///
+    /// Panics when the current test does not consume the resource.
+    #[track_caller]
+    pub fn write(&self) -> RwLockWriteGuard<'_, T> {
+        self.check_access(true);
+
+        self.value.write().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+impl <T> From<&Resource<T>> for ResourceId {
+    fn from(val: &Resource<T>) -> Self {
+        val.id()
+    }
+}
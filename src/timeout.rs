@@ -15,6 +15,16 @@ impl Timeout {
     pub fn duration(self) -> Duration {
         self.0
     }
+
+    /// Reads a milliseconds value from the environment variable `name`
+    pub(crate) fn from_env(name: &str) -> Option<Self> {
+        let val = std::env::var(name).ok()?;
+
+        match val.parse::<u64>() {
+            Ok(v)	=> Some(v.into()),
+            Err(e)	=> panic!("bad value {val:?} in ${name}: {e}"),
+        }
+    }
 }
 
 /// Converts a milliseconds value in a [`Timeout`]